@@ -1,22 +1,48 @@
 #![forbid(unsafe_code)]
 
 use {
-    organyacat::{Interpolation, Player},
+    organyacat::{Interpolation, PiyoSong, Player, Song, Soundbank, WavOptions},
     std::{
         error::Error,
-        io::{IsTerminal, Write},
+        fs::File,
+        io::{BufWriter, IsTerminal, Write},
+        time::Duration,
     },
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut args = std::env::args().skip(1);
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if_eq("info").is_some() {
+        let org_path = args.next().expect("Need org file");
+        return info(&org_path);
+    }
     let sb_path = args.next().expect("Need soundbank file");
     let org_path = args.next().expect("Need org file");
     let mut buffer: [f32; 256] = [0.0; _];
     let mut player = Player::default();
 
     player.load_soundbank_file(sb_path.as_ref())?;
-    player.load_song_file(org_path.as_ref())?;
+    if org_path.ends_with(".pmd") {
+        let piyo = PiyoSong::load_file(org_path.as_ref())?;
+        let (song, soundbank) = piyo.to_song(player.soundbank());
+        player.set_soundbank(soundbank);
+        player.set_song(song);
+    } else {
+        player.load_song_file(org_path.as_ref())?;
+    }
+    for (channel, instrument) in player.unresolved_drums() {
+        eprintln!("Warning: channel {channel} uses missing drum {instrument}");
+    }
+
+    if let Some(wav_path) = args.next() {
+        let options = WavOptions {
+            loops: 2,
+            fade_out: Duration::from_secs(5),
+            ..WavOptions::default()
+        };
+        player.write_wav(BufWriter::new(File::create(wav_path)?), &options)?;
+        return Ok(());
+    }
 
     let mut writer = std::io::stdout().lock();
     if writer.is_terminal() {
@@ -28,3 +54,50 @@ fn main() -> Result<(), Box<dyn Error>> {
         writer.write_all(bytemuck::cast_slice_mut(&mut buffer))?;
     }
 }
+
+/// Print a summary of a song
+fn info(org_path: &str) -> Result<(), Box<dyn Error>> {
+    let song = if org_path.ends_with(".pmd") {
+        PiyoSong::load_file(org_path.as_ref())?
+            .to_song(&Soundbank::default())
+            .0
+    } else {
+        let mut song = Song::default();
+        song.read(&std::fs::read(org_path)?)?;
+        song
+    };
+    let stats = song.stats();
+    println!("Tempo: {} ms per tick, {:.1} BPM", song.tempo_ms, stats.bpm);
+    println!(
+        "Meter: {} beats per measure, {} steps per beat",
+        song.beats_per_measure, song.steps_per_beat
+    );
+    println!(
+        "Intro: {} ticks, {:.2} s",
+        stats.intro_ticks,
+        stats.intro_duration.as_secs_f64()
+    );
+    println!(
+        "Loop: {} ticks, {:.2} s",
+        stats.loop_ticks,
+        stats.loop_duration.as_secs_f64()
+    );
+    println!(
+        "Measures: {} occupied of {}",
+        stats.occupied_measures, stats.measures
+    );
+    println!("Melody instruments: {:?}", stats.melody_instruments);
+    println!("Drum instruments: {:?}", stats.drum_instruments);
+    for (i, ch) in stats.channels.iter().enumerate() {
+        if let Some(range) = &ch.pitch_range {
+            println!(
+                "Channel {i}: {} notes, pitch {}..={}, instrument {}",
+                ch.notes,
+                range.start(),
+                range.end(),
+                ch.instrument
+            );
+        }
+    }
+    Ok(())
+}
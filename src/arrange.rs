@@ -0,0 +1,82 @@
+use crate::song::Song;
+
+/// Timeline edits
+///
+/// These shift every channel together, along with the repeat points. Ranges start at a tick and
+/// span a number of measures.
+impl Song {
+    /// Length of a measure in ticks
+    #[must_use]
+    pub fn measure_len(&self) -> u32 {
+        (u32::from(self.beats_per_measure) * u32::from(self.steps_per_beat)).max(1)
+    }
+    /// Insert `count` blank measures at `at`
+    ///
+    /// Events and repeat points at or after `at` move later. Notes that started before `at` keep
+    /// their length.
+    pub fn insert_measures(&mut self, at: u32, count: u32) {
+        let len = count.saturating_mul(self.measure_len());
+        let shift = |pos: &mut u32| {
+            if *pos >= at {
+                *pos = pos.saturating_add(len);
+            }
+        };
+        for ch in &mut self.channels {
+            for event in &mut ch.events {
+                shift(&mut event.position);
+            }
+        }
+        shift(&mut self.repeat_start);
+        shift(&mut self.repeat_end);
+    }
+    /// Delete `count` measures starting at `at`
+    ///
+    /// Events in the range are removed, and the following ones move earlier. Notes that started
+    /// before `at` are shortened to end there. Repeat points in the range move to `at`.
+    pub fn delete_measures(&mut self, at: u32, count: u32) {
+        let end = at.saturating_add(count.saturating_mul(self.measure_len()));
+        let len = end - at;
+        let shift = |pos: u32| {
+            if pos >= end { pos - len } else { pos.min(at) }
+        };
+        for ch in &mut self.channels {
+            ch.events.retain(|e| !(at..end).contains(&e.position));
+            for event in &mut ch.events {
+                if event.position < at {
+                    let room = at - event.position;
+                    if u32::from(event.length) > room {
+                        // Less than the length, so it fits
+                        event.length = u8::try_from(room).unwrap_or(u8::MAX);
+                    }
+                } else {
+                    event.position -= len;
+                }
+            }
+        }
+        self.repeat_start = shift(self.repeat_start);
+        self.repeat_end = shift(self.repeat_end).max(self.repeat_start.saturating_add(1));
+    }
+    /// Repeat the `count` measures starting at `at` right after themselves
+    ///
+    /// Events and repeat points after the section move later to make room for the copy.
+    pub fn duplicate_measures(&mut self, at: u32, count: u32) {
+        let end = at.saturating_add(count.saturating_mul(self.measure_len()));
+        let len = end - at;
+        self.insert_measures(end, count);
+        for ch in &mut self.channels {
+            let copies: Vec<_> = ch
+                .events
+                .iter()
+                .filter(|e| (at..end).contains(&e.position))
+                .map(|e| {
+                    let mut copy = *e;
+                    copy.position = copy.position.saturating_add(len);
+                    copy
+                })
+                .collect();
+            for copy in copies {
+                ch.insert_event(copy);
+            }
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use {
+    crate::{
+        OrgError,
+        song::{Event, Song},
+        text::EventLine,
+    },
+    std::fmt,
+};
+
+/// A difference between two songs, part of a [`SongDiff`]
+///
+/// Each change holds the value before and after it, so it can be checked against the song it
+/// is applied to, and reversed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SongChange {
+    /// The tempo changed
+    TempoMs {
+        /// Value before
+        old: u16,
+        /// Value after
+        new: u16,
+    },
+    /// The beats per measure changed
+    BeatsPerMeasure {
+        /// Value before
+        old: u8,
+        /// Value after
+        new: u8,
+    },
+    /// The steps per beat changed
+    StepsPerBeat {
+        /// Value before
+        old: u8,
+        /// Value after
+        new: u8,
+    },
+    /// The repeat start changed
+    RepeatStart {
+        /// Value before
+        old: u32,
+        /// Value after
+        new: u32,
+    },
+    /// The repeat end changed
+    RepeatEnd {
+        /// Value before
+        old: u32,
+        /// Value after
+        new: u32,
+    },
+    /// The instrument of a channel changed
+    Instrument {
+        /// Index of the channel
+        channel: usize,
+        /// Value before
+        old: u8,
+        /// Value after
+        new: u8,
+    },
+    /// The finetune of a channel changed
+    Finetune {
+        /// Index of the channel
+        channel: usize,
+        /// Value before
+        old: u16,
+        /// Value after
+        new: u16,
+    },
+    /// Whether the notes of a channel are pizzicato changed
+    Pizzicato {
+        /// Index of the channel
+        channel: usize,
+        /// Value before
+        old: bool,
+        /// Value after
+        new: bool,
+    },
+    /// An event was added, removed or modified
+    Event {
+        /// Index of the channel
+        channel: usize,
+        /// Position of the event
+        position: u32,
+        /// The event before, or `None` if it was added
+        old: Option<Event>,
+        /// The event after, or `None` if it was removed
+        new: Option<Event>,
+    },
+}
+
+impl SongChange {
+    /// The change that undoes this one
+    #[must_use]
+    pub const fn reverse(self) -> Self {
+        match self {
+            Self::TempoMs { old, new } => Self::TempoMs { old: new, new: old },
+            Self::BeatsPerMeasure { old, new } => Self::BeatsPerMeasure { old: new, new: old },
+            Self::StepsPerBeat { old, new } => Self::StepsPerBeat { old: new, new: old },
+            Self::RepeatStart { old, new } => Self::RepeatStart { old: new, new: old },
+            Self::RepeatEnd { old, new } => Self::RepeatEnd { old: new, new: old },
+            Self::Instrument { channel, old, new } => Self::Instrument {
+                channel,
+                old: new,
+                new: old,
+            },
+            Self::Finetune { channel, old, new } => Self::Finetune {
+                channel,
+                old: new,
+                new: old,
+            },
+            Self::Pizzicato { channel, old, new } => Self::Pizzicato {
+                channel,
+                old: new,
+                new: old,
+            },
+            Self::Event {
+                channel,
+                position,
+                old,
+                new,
+            } => Self::Event {
+                channel,
+                position,
+                old: new,
+                new: old,
+            },
+        }
+    }
+    /// Whether `song` has the value this change starts from
+    fn applies_to(&self, song: &Song) -> bool {
+        let channel = |channel: usize| song.channels.get(channel);
+        match *self {
+            Self::TempoMs { old, .. } => song.tempo_ms == old,
+            Self::BeatsPerMeasure { old, .. } => song.beats_per_measure == old,
+            Self::StepsPerBeat { old, .. } => song.steps_per_beat == old,
+            Self::RepeatStart { old, .. } => song.repeat_start == old,
+            Self::RepeatEnd { old, .. } => song.repeat_end == old,
+            Self::Instrument {
+                channel: i, old, ..
+            } => channel(i).is_some_and(|ch| ch.instrument == old),
+            Self::Finetune {
+                channel: i, old, ..
+            } => channel(i).is_some_and(|ch| ch.finetune == old),
+            Self::Pizzicato {
+                channel: i, old, ..
+            } => channel(i).is_some_and(|ch| ch.pizzicato == old),
+            Self::Event {
+                channel: i,
+                position,
+                old,
+                ..
+            } => channel(i).is_some_and(|ch| ch.event_at(position) == old.as_ref()),
+        }
+    }
+    /// Set the value after the change, which must apply to `song`
+    fn apply(&self, song: &mut Song) {
+        match *self {
+            Self::TempoMs { new, .. } => song.tempo_ms = new,
+            Self::BeatsPerMeasure { new, .. } => song.beats_per_measure = new,
+            Self::StepsPerBeat { new, .. } => song.steps_per_beat = new,
+            Self::RepeatStart { new, .. } => song.repeat_start = new,
+            Self::RepeatEnd { new, .. } => song.repeat_end = new,
+            Self::Instrument { channel, new, .. } => song.channels[channel].instrument = new,
+            Self::Finetune { channel, new, .. } => song.channels[channel].finetune = new,
+            Self::Pizzicato { channel, new, .. } => song.channels[channel].pizzicato = new,
+            Self::Event {
+                channel,
+                position,
+                new,
+                ..
+            } => {
+                let events = &mut song.channels[channel].events;
+                // Events are set as they are, even the ones that change nothing
+                match (events.binary_search_by_key(&position, |e| e.position), new) {
+                    (Ok(index), Some(event)) => events[index] = event,
+                    (Ok(index), None) => {
+                        events.remove(index);
+                    }
+                    (Err(index), Some(event)) => events.insert(index, event),
+                    (Err(_), None) => {}
+                }
+            }
+        }
+    }
+}
+
+/// The changes that turn a song into another, made by [`Song::diff`]
+///
+/// It can be applied as a patch to the song it was made from, or to a song that only differs
+/// where the changes don't touch. It displays as a line per change, for review.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SongDiff {
+    /// The changes, header fields first, then the settings and events of each channel in
+    /// order, events by position
+    pub changes: Vec<SongChange>,
+}
+
+impl SongDiff {
+    /// Whether the songs are the same
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    /// The diff that undoes this one
+    #[must_use]
+    pub fn reverse(&self) -> Self {
+        Self {
+            changes: self.changes.iter().map(|c| c.reverse()).collect(),
+        }
+    }
+    /// The changes that don't apply to `song`, because it doesn't have the values they start
+    /// from
+    #[must_use]
+    pub fn conflicts(&self, song: &Song) -> Vec<SongChange> {
+        self.changes
+            .iter()
+            .filter(|c| !c.applies_to(song))
+            .copied()
+            .collect()
+    }
+    /// Apply the changes to `song`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::PatchConflict`] with the first change that doesn't apply to `song`.
+    /// The song is left unchanged. [`Self::conflicts`] lists all of them.
+    pub fn apply(&self, song: &mut Song) -> Result<(), OrgError> {
+        if let Some(index) = self.changes.iter().position(|c| !c.applies_to(song)) {
+            return Err(OrgError::PatchConflict { change: index });
+        }
+        for change in &self.changes {
+            change.apply(song);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SongDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match *change {
+                SongChange::TempoMs { old, new } => writeln!(f, "tempo_ms {old} -> {new}")?,
+                SongChange::BeatsPerMeasure { old, new } => {
+                    writeln!(f, "beats_per_measure {old} -> {new}")?;
+                }
+                SongChange::StepsPerBeat { old, new } => {
+                    writeln!(f, "steps_per_beat {old} -> {new}")?;
+                }
+                SongChange::RepeatStart { old, new } => {
+                    writeln!(f, "repeat_start {old} -> {new}")?;
+                }
+                SongChange::RepeatEnd { old, new } => writeln!(f, "repeat_end {old} -> {new}")?,
+                SongChange::Instrument { channel, old, new } => {
+                    writeln!(f, "channel {channel} instrument {old} -> {new}")?;
+                }
+                SongChange::Finetune { channel, old, new } => {
+                    writeln!(f, "channel {channel} finetune {old} -> {new}")?;
+                }
+                SongChange::Pizzicato { channel, old, new } => writeln!(
+                    f,
+                    "channel {channel} pizzicato {} -> {}",
+                    u8::from(old),
+                    u8::from(new)
+                )?,
+                SongChange::Event {
+                    channel, old, new, ..
+                } => match (old, new) {
+                    (None, Some(new)) => writeln!(f, "channel {channel} + {}", EventLine(&new))?,
+                    (Some(old), None) => writeln!(f, "channel {channel} - {}", EventLine(&old))?,
+                    (Some(old), Some(new)) => writeln!(
+                        f,
+                        "channel {channel} ~ {} -> {}",
+                        EventLine(&old),
+                        EventLine(&new)
+                    )?,
+                    (None, None) => {}
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Song {
+    /// The changes that turn this song into `other`
+    ///
+    /// Events are matched by channel and position, so the events of each channel should be
+    /// normalized, like [`Channel::normalize`](crate::Channel::normalize) does.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> SongDiff {
+        let mut changes = Vec::new();
+        macro_rules! field {
+            ($field:ident, $variant:ident) => {
+                if self.$field != other.$field {
+                    changes.push(SongChange::$variant {
+                        old: self.$field,
+                        new: other.$field,
+                    });
+                }
+            };
+        }
+        field!(tempo_ms, TempoMs);
+        field!(beats_per_measure, BeatsPerMeasure);
+        field!(steps_per_beat, StepsPerBeat);
+        field!(repeat_start, RepeatStart);
+        field!(repeat_end, RepeatEnd);
+        for (channel, (a, b)) in self.channels.iter().zip(&other.channels).enumerate() {
+            macro_rules! setting {
+                ($field:ident, $variant:ident) => {
+                    if a.$field != b.$field {
+                        changes.push(SongChange::$variant {
+                            channel,
+                            old: a.$field,
+                            new: b.$field,
+                        });
+                    }
+                };
+            }
+            setting!(instrument, Instrument);
+            setting!(finetune, Finetune);
+            setting!(pizzicato, Pizzicato);
+            let mut old = a.events.iter().peekable();
+            let mut new = b.events.iter().peekable();
+            loop {
+                let (old_event, new_event) = match (old.peek(), new.peek()) {
+                    (None, None) => break,
+                    (Some(o), Some(n)) if o.position == n.position => {
+                        (old.next().copied(), new.next().copied())
+                    }
+                    (Some(o), Some(n)) if o.position < n.position => (old.next().copied(), None),
+                    (Some(_), None) => (old.next().copied(), None),
+                    (_, Some(_)) => (None, new.next().copied()),
+                };
+                if old_event != new_event {
+                    let position = old_event.or(new_event).map_or(0, |e| e.position);
+                    changes.push(SongChange::Event {
+                        channel,
+                        position,
+                        old: old_event,
+                        new: new_event,
+                    });
+                }
+            }
+        }
+        SongDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::random_song};
+
+    #[test]
+    fn patch_round_trip() {
+        for seed in 0..8 {
+            let old = random_song(seed);
+            let mut new = random_song(seed + 100);
+            new.steps_per_beat = 3;
+            new.channels[5].pizzicato = !old.channels[5].pizzicato;
+            let diff = old.diff(&new);
+            assert!(!diff.is_empty());
+            assert!(old.diff(&old).is_empty());
+            let mut patched = old.clone();
+            diff.apply(&mut patched).unwrap();
+            assert_eq!(patched, new);
+            diff.reverse().apply(&mut patched).unwrap();
+            assert_eq!(patched, old);
+        }
+    }
+
+    #[test]
+    fn conflicts_leave_song_unchanged() {
+        let old = random_song(1);
+        let channel = old.channels.iter().position(|ch| !ch.events.is_empty());
+        let channel = channel.unwrap();
+        let mut new = old.clone();
+        new.tempo_ms += 10;
+        new.channels[channel].events[0].length += 1;
+        new.channels[channel].events.pop();
+        let diff = old.diff(&new);
+        assert_eq!(diff.changes.len(), 3);
+        // Another edit of the tempo and of the first event
+        let mut other = old.clone();
+        other.tempo_ms += 1;
+        other.channels[channel].events[0].length += 2;
+        assert_eq!(diff.conflicts(&other), diff.changes[..2]);
+        let before = other.clone();
+        assert!(matches!(
+            diff.apply(&mut other),
+            Err(OrgError::PatchConflict { change: 0 })
+        ));
+        assert_eq!(other, before);
+    }
+}
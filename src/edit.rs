@@ -0,0 +1,206 @@
+use crate::{
+    PROPERTY_UNUSED,
+    song::{Channel, Event},
+};
+
+impl Event {
+    /// An event at `position` that changes nothing
+    #[must_use]
+    pub const fn empty(position: u32) -> Self {
+        Self {
+            position,
+            pitch: PROPERTY_UNUSED,
+            length: 1,
+            volume: PROPERTY_UNUSED,
+            pan: PROPERTY_UNUSED,
+        }
+    }
+    /// Whether the event doesn't play a note, nor change the volume or the pan
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.pitch == PROPERTY_UNUSED
+            && self.volume == PROPERTY_UNUSED
+            && self.pan == PROPERTY_UNUSED
+    }
+    /// Replace the values the player can't play, like [`Song::read`](crate::Song::read) does
+    ///
+    /// Pitches from 96 are unused, a length of 0 is 1, and pans above 12 are centered.
+    pub(crate) const fn clean_up(&mut self) {
+        if self.pitch >= 96 {
+            self.pitch = PROPERTY_UNUSED;
+        }
+        if self.length == 0 {
+            self.length = 1;
+        }
+        if self.pan > 12 && self.pan != PROPERTY_UNUSED {
+            self.pan = 6;
+        }
+    }
+    /// Apply the used properties of `other` on top of this event. The position is kept.
+    pub const fn merge(&mut self, other: &Self) {
+        if other.pitch != PROPERTY_UNUSED {
+            self.pitch = other.pitch;
+            self.length = other.length;
+        }
+        if other.volume != PROPERTY_UNUSED {
+            self.volume = other.volume;
+        }
+        if other.pan != PROPERTY_UNUSED {
+            self.pan = other.pan;
+        }
+    }
+}
+
+/// Editing operations
+///
+/// The player expects the events of a channel to be sorted by position, with at most one event
+/// per position. These operations keep it that way, merging events that land on the same
+/// position. Events that end up changing nothing are removed. They expect the events to be
+/// normalized already, which [`Self::normalize`] does. Values the player can't play are
+/// replaced like [`Song::read`](crate::Song::read) does: pitches from 96 remove the note, and
+/// pans above 12 are centered.
+impl Channel {
+    /// The finetune of the channel
+    #[must_use]
+    pub const fn finetune(&self) -> u16 {
+        self.finetune
+    }
+    /// Whether the notes of the channel are played pizzicato
+    #[must_use]
+    pub const fn pizzicato(&self) -> bool {
+        self.pizzicato
+    }
+    /// The event at `position`, if any
+    #[must_use]
+    pub fn event_at(&self, position: u32) -> Option<&Event> {
+        self.events
+            .binary_search_by_key(&position, |e| e.position)
+            .ok()
+            .map(|index| &self.events[index])
+    }
+    /// Insert an event, merging it into the event already at its position
+    ///
+    /// Used properties of `event` replace the ones of the existing event.
+    pub fn insert_event(&mut self, mut event: Event) {
+        event.clean_up();
+        self.update(event.position, |existing| existing.merge(&event));
+    }
+    /// Remove the event at `position`, and return it
+    pub fn remove_event(&mut self, position: u32) -> Option<Event> {
+        let index = self
+            .events
+            .binary_search_by_key(&position, |e| e.position)
+            .ok()?;
+        Some(self.events.remove(index))
+    }
+    /// Move the event at `from` to `to`, merging it into the event already there
+    ///
+    /// Returns `false` if there is no event at `from`.
+    pub fn move_event(&mut self, from: u32, to: u32) -> bool {
+        let Some(mut event) = self.remove_event(from) else {
+            return false;
+        };
+        event.position = to;
+        self.insert_event(event);
+        true
+    }
+    /// Play a note of `pitch` for `length` ticks at `position`, or remove the note with
+    /// [`PROPERTY_UNUSED`](crate::PROPERTY_UNUSED)
+    pub fn set_note(&mut self, position: u32, pitch: u8, length: u8) {
+        self.update(position, |event| {
+            event.pitch = pitch;
+            event.length = length;
+            event.clean_up();
+        });
+    }
+    /// Change the volume at `position`, or remove the change with
+    /// [`PROPERTY_UNUSED`](crate::PROPERTY_UNUSED)
+    pub fn set_volume(&mut self, position: u32, volume: u8) {
+        self.update(position, |event| event.volume = volume);
+    }
+    /// Change the pan at `position`, or remove the change with
+    /// [`PROPERTY_UNUSED`](crate::PROPERTY_UNUSED)
+    pub fn set_pan(&mut self, position: u32, pan: u8) {
+        self.update(position, |event| {
+            event.pan = pan;
+            event.clean_up();
+        });
+    }
+    /// Whether the events are sorted by position, with at most one event per position
+    #[must_use]
+    pub fn is_normalized(&self) -> bool {
+        self.events
+            .windows(2)
+            .all(|pair| pair[0].position < pair[1].position)
+    }
+    /// Sort the events by position, and merge events at the same position
+    ///
+    /// Events at the same position are merged in order, so later events take precedence.
+    /// Events that change nothing are removed.
+    pub fn normalize(&mut self) {
+        self.events.sort_by_key(|e| e.position);
+        let mut merged: Vec<Event> = Vec::with_capacity(self.events.len());
+        for event in self.events.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.position == event.position => last.merge(&event),
+                _ => merged.push(event),
+            }
+        }
+        merged.retain(|e| !e.is_empty());
+        self.events = merged;
+    }
+    /// Modify the event at `position`, creating it if needed, and remove it if it ends up
+    /// changing nothing
+    fn update(&mut self, position: u32, f: impl FnOnce(&mut Event)) {
+        let index = match self.events.binary_search_by_key(&position, |e| e.position) {
+            Ok(index) => index,
+            Err(index) => {
+                self.events.insert(index, Event::empty(position));
+                index
+            }
+        };
+        f(&mut self.events[index]);
+        if self.events[index].is_empty() {
+            self.events.remove(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::render};
+
+    #[test]
+    fn unplayable_values_are_cleaned_up() {
+        let mut song = crate::Song::default();
+        let ch = &mut song.channels[0];
+        ch.insert_event(Event {
+            position: 0,
+            pitch: 100,
+            length: 0,
+            volume: 200,
+            pan: 13,
+        });
+        ch.set_note(1, 96, 4);
+        ch.set_note(2, 95, 0);
+        ch.set_pan(2, 200);
+        let expected = [
+            Event {
+                position: 0,
+                pitch: PROPERTY_UNUSED,
+                length: 1,
+                volume: 200,
+                pan: 6,
+            },
+            Event {
+                position: 2,
+                pitch: 95,
+                length: 1,
+                volume: PROPERTY_UNUSED,
+                pan: 6,
+            },
+        ];
+        assert_eq!(ch.events, expected);
+        render(&song, 4410);
+    }
+}
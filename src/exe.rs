@@ -0,0 +1,318 @@
+use {
+    crate::{OrgError, song::Song, soundbank::CLASSIC_DRUM_COUNT},
+    std::path::Path,
+};
+
+/// Resource directories nest type, name and language, in that order
+const MAX_RESOURCE_DEPTH: u8 = 3;
+
+/// Resources extracted from a Cave Story executable (`Doukutsu.exe`)
+///
+/// The freeware version embeds its songs as `ORG` resources, and the melody waveforms as the
+/// `WAVE100` resource. The drum samples are synthesized by the game at startup from PixTone
+/// parameters stored in its code, so they are not part of the resources, and are not
+/// extracted.
+pub struct ExeResources {
+    /// The songs found in `ORG` resources, along with their resource names
+    pub songs: Vec<(String, Song)>,
+    /// The names of the `ORG` resources that aren't valid Organya songs
+    pub malformed_songs: Vec<String>,
+    /// Soundbank built from the `WAVE100` resource, in the format accepted by
+    /// [`Player::read_soundbank`](crate::Player::read_soundbank). `None` if the executable
+    /// has no `WAVE100` resource.
+    ///
+    /// Its drums are empty, so they play silence. To hear them, replace
+    /// [`Soundbank::drums`](crate::Soundbank::drums) with the drums of another soundbank.
+    pub soundbank: Option<Vec<u8>>,
+}
+
+impl ExeResources {
+    /// Extract the songs and the soundbank from the raw bytes of a PE executable
+    ///
+    /// `ORG` resources that aren't valid songs are skipped, and listed in
+    /// [`Self::malformed_songs`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::MalformedExe`] if the data isn't a valid PE executable, or the
+    /// `WAVE100` resource is too short.
+    pub fn read(data: &[u8]) -> Result<Self, OrgError> {
+        let pe = PeImage::parse(data).ok_or(OrgError::MalformedExe)?;
+        let mut resources = Vec::new();
+        if let Some(root) = pe.resource_root {
+            pe.collect_resources(root, 0, &mut Vec::new(), &mut resources)
+                .ok_or(OrgError::MalformedExe)?;
+        }
+        let mut songs = Vec::new();
+        let mut malformed_songs = Vec::new();
+        let mut soundbank = None;
+        for res in resources {
+            match (res.kind.as_str(), res.name.as_str()) {
+                ("ORG", _) => {
+                    let mut song = Song::default();
+                    match song.read(res.data) {
+                        Ok(()) => songs.push((res.name, song)),
+                        Err(_) => malformed_songs.push(res.name),
+                    }
+                }
+                ("WAVE", "WAVE100") => soundbank = Some(classic_soundbank(res.data)?),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            songs,
+            malformed_songs,
+            soundbank,
+        })
+    }
+    /// Extract the songs and the soundbank from an executable file. See [`Self::read`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`std::io::Error`] if reading the file failed.
+    /// - Returns [`OrgError::MalformedExe`] if the data isn't a valid PE executable, or the
+    ///   `WAVE100` resource is too short.
+    pub fn load_file(file_path: &Path) -> Result<Self, OrgError> {
+        let buffer = std::fs::read(file_path)?;
+        Self::read(&buffer)
+    }
+}
+
+/// Build a soundbank with the given melody waves and no drum samples
+fn classic_soundbank(wave100: &[u8]) -> Result<Vec<u8>, OrgError> {
+    let Some(waves) = wave100.get(..25_600) else {
+        return Err(OrgError::MalformedExe);
+    };
+    let mut bank = waves.to_vec();
+    bank.resize(bank.len() + CLASSIC_DRUM_COUNT * 4, 0);
+    Ok(bank)
+}
+
+struct Resource<'a> {
+    kind: String,
+    name: String,
+    data: &'a [u8],
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_offset: u32,
+}
+
+struct PeImage<'a> {
+    data: &'a [u8],
+    sections: Vec<Section>,
+    /// File offset of the resource directory
+    resource_root: Option<usize>,
+}
+
+impl<'a> PeImage<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.get(..2)? != b"MZ" {
+            return None;
+        }
+        let pe_offset = usize::try_from(u32_at(data, 0x3C)?).ok()?;
+        if data.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+            return None;
+        }
+        let coff = pe_offset + 4;
+        let section_count = u16_at(data, coff + 2)?;
+        let optional_header_size = usize::from(u16_at(data, coff + 16)?);
+        let optional = coff + 20;
+        let directories = match u16_at(data, optional)? {
+            0x10b => optional + 96,
+            0x20b => optional + 112,
+            _ => return None,
+        };
+        let directory_count = u32_at(data, directories - 4)?;
+        let mut sections = Vec::new();
+        let table = optional + optional_header_size;
+        for i in 0..usize::from(section_count) {
+            let header = table + i * 40;
+            sections.push(Section {
+                virtual_size: u32_at(data, header + 8)?.max(u32_at(data, header + 16)?),
+                virtual_address: u32_at(data, header + 12)?,
+                raw_offset: u32_at(data, header + 20)?,
+            });
+        }
+        let mut this = Self {
+            data,
+            sections,
+            resource_root: None,
+        };
+        // The resource directory is the third data directory
+        if directory_count > 2 {
+            let rva = u32_at(data, directories + 2 * 8)?;
+            if rva != 0 {
+                this.resource_root = Some(this.rva_to_offset(rva)?);
+            }
+        }
+        Some(this)
+    }
+
+    fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        let section = self
+            .sections
+            .iter()
+            .find(|s| rva >= s.virtual_address && rva - s.virtual_address < s.virtual_size)?;
+        usize::try_from(
+            section
+                .raw_offset
+                .checked_add(rva - section.virtual_address)?,
+        )
+        .ok()
+    }
+
+    fn collect_resources(
+        &self,
+        dir: usize,
+        depth: u8,
+        path: &mut Vec<String>,
+        out: &mut Vec<Resource<'a>>,
+    ) -> Option<()> {
+        let root = self.resource_root?;
+        let named = u16_at(self.data, dir + 12)?;
+        let ids = u16_at(self.data, dir + 14)?;
+        for i in 0..usize::from(named) + usize::from(ids) {
+            let entry = dir + 16 + i * 8;
+            let name = u32_at(self.data, entry)?;
+            let offset = u32_at(self.data, entry + 4)?;
+            let name = if name & 0x8000_0000 == 0 {
+                name.to_string()
+            } else {
+                self.resource_name(root + usize::try_from(name & 0x7FFF_FFFF).ok()?)?
+            };
+            let target = root + usize::try_from(offset & 0x7FFF_FFFF).ok()?;
+            if offset & 0x8000_0000 != 0 {
+                if depth + 1 >= MAX_RESOURCE_DEPTH {
+                    return None;
+                }
+                path.push(name);
+                self.collect_resources(target, depth + 1, path, out)?;
+                path.pop();
+            } else if let [kind, name, ..] = path.as_slice() {
+                let rva = u32_at(self.data, target)?;
+                let size = usize::try_from(u32_at(self.data, target + 4)?).ok()?;
+                let start = self.rva_to_offset(rva)?;
+                out.push(Resource {
+                    kind: kind.clone(),
+                    name: name.clone(),
+                    data: self.data.get(start..start.checked_add(size)?)?,
+                });
+            }
+        }
+        Some(())
+    }
+
+    fn resource_name(&self, offset: usize) -> Option<String> {
+        let len = usize::from(u16_at(self.data, offset)?);
+        let units = (0..len)
+            .map(|i| u16_at(self.data, offset + 2 + i * 2))
+            .collect::<Option<Vec<_>>>()?;
+        String::from_utf16(&units).ok()
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..)?
+        .first_chunk()
+        .copied()
+        .map(u16::from_le_bytes)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..)?
+        .first_chunk()
+        .copied()
+        .map(u32::from_le_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::soundbank::Soundbank};
+
+    /// Virtual address and file offset of the resource section
+    const SECTION_RVA: usize = 0x1000;
+    const SECTION_OFFSET: usize = 0x200;
+
+    fn put_u16(out: &mut [u8], offset: usize, value: u16) {
+        out[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+    fn put_u32(out: &mut [u8], offset: usize, value: usize) {
+        let value = u32::try_from(value).unwrap();
+        out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A PE32 executable with a resource per `(type, name, data)`
+    fn executable(resources: &[(&str, &str, &[u8])]) -> Vec<u8> {
+        let count = resources.len();
+        // Root directory, then a name and a language directory, and a data entry per resource
+        let per_resource = 24 + 24 + 16;
+        let mut section = vec![0; 16 + 8 * count + per_resource * count];
+        put_u16(&mut section, 12, u16::try_from(count).unwrap());
+        let string = |section: &mut Vec<u8>, text: &str| {
+            let offset = section.len();
+            let units: Vec<u16> = text.encode_utf16().collect();
+            section.extend_from_slice(&u16::try_from(units.len()).unwrap().to_le_bytes());
+            section.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+            offset | 0x8000_0000
+        };
+        for (i, (kind, name, data)) in resources.iter().enumerate() {
+            let name_dir = 16 + 8 * count + per_resource * i;
+            let language_dir = name_dir + 24;
+            let data_entry = language_dir + 24;
+            let kind = string(&mut section, kind);
+            let name = string(&mut section, name);
+            put_u32(&mut section, 16 + 8 * i, kind);
+            put_u32(&mut section, 20 + 8 * i, name_dir | 0x8000_0000);
+            put_u16(&mut section, name_dir + 12, 1);
+            put_u32(&mut section, name_dir + 16, name);
+            put_u32(&mut section, name_dir + 20, language_dir | 0x8000_0000);
+            put_u16(&mut section, language_dir + 14, 1);
+            put_u32(&mut section, language_dir + 16, 1041);
+            put_u32(&mut section, language_dir + 20, data_entry);
+            let rva = SECTION_RVA + section.len();
+            put_u32(&mut section, data_entry, rva);
+            put_u32(&mut section, data_entry + 4, data.len());
+            section.extend_from_slice(data);
+        }
+        let mut out = vec![0; SECTION_OFFSET];
+        out[..2].copy_from_slice(b"MZ");
+        put_u32(&mut out, 0x3C, 0x40);
+        out[0x40..0x44].copy_from_slice(b"PE\0\0");
+        let coff = 0x44;
+        put_u16(&mut out, coff + 2, 1);
+        put_u16(&mut out, coff + 16, 0xE0);
+        let optional = coff + 20;
+        put_u16(&mut out, optional, 0x10b);
+        let directories = optional + 96;
+        put_u32(&mut out, directories - 4, 16);
+        put_u32(&mut out, directories + 16, SECTION_RVA);
+        put_u32(&mut out, directories + 20, section.len());
+        let header = optional + 0xE0;
+        put_u32(&mut out, header + 8, section.len());
+        put_u32(&mut out, header + 12, SECTION_RVA);
+        put_u32(&mut out, header + 16, section.len());
+        put_u32(&mut out, header + 20, SECTION_OFFSET);
+        out.extend_from_slice(&section);
+        out
+    }
+
+    #[test]
+    fn malformed_songs_are_skipped() {
+        let mut song = b"Org-02".to_vec();
+        song.resize(114, 0);
+        let exe = executable(&[
+            ("ORG", "BAD", b"Org-99"),
+            ("ORG", "GOOD", &song),
+            ("WAVE", "WAVE100", &[0; 25_600]),
+        ]);
+        let resources = ExeResources::read(&exe).unwrap();
+        let names: Vec<_> = resources.songs.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["GOOD"]);
+        assert_eq!(resources.malformed_songs, ["BAD"]);
+        let bank = Soundbank::read(&resources.soundbank.unwrap()).unwrap();
+        assert_eq!(bank.drums.len(), CLASSIC_DRUM_COUNT);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    PROPERTY_UNUSED,
+    lint::LintKind,
+    song::{Channel, Song},
+};
+
+/// Number of times lints are fixed again, in case fixes reveal new ones
+const MAX_FIX_ROUNDS: usize = 4;
+
+impl Song {
+    /// Remove the differences the player can't hear
+    ///
+    /// This fixes every lint of [`Self::lint`] that has a fix. Channels without events get
+    /// the default settings. The finetune and pizzicato setting of drum channels, and the
+    /// lengths the player ignores are reset. The song sounds the same afterwards.
+    pub fn canonicalize(&mut self) {
+        for _ in 0..MAX_FIX_ROUNDS {
+            let lints: Vec<_> = self
+                .lint()
+                .into_iter()
+                .filter(|l| l.kind != LintKind::Unsorted)
+                .collect();
+            if lints.is_empty() {
+                break;
+            }
+            self.fix_lints(&lints);
+        }
+        let defaults = Self::default();
+        for (i, (ch, default)) in self.channels.iter_mut().zip(defaults.channels).enumerate() {
+            let melody = i < 8;
+            if ch.events.is_empty() {
+                *ch = default;
+                continue;
+            }
+            if !melody {
+                ch.finetune = default.finetune;
+                ch.pizzicato = false;
+            }
+            // Drums and pizzicato notes play until their sample ends
+            let ignores_length = !melody || ch.pizzicato;
+            for event in &mut ch.events {
+                if ignores_length || event.pitch == PROPERTY_UNUSED {
+                    event.length = 1;
+                }
+            }
+        }
+    }
+    /// A hash of how the song sounds, to find duplicates
+    ///
+    /// Songs that only differ in ways [`Self::canonicalize`] removes, or in their beats per
+    /// measure and steps per beat, have the same fingerprint. The hash is 64 bit FNV-1a, and
+    /// stays the same across versions of the library and platforms.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut song = self.clone();
+        song.canonicalize();
+        let mut hash = Fnv1a::default();
+        hash.write(&song.tempo_ms.to_le_bytes());
+        hash.write(&song.repeat_start.to_le_bytes());
+        hash.write(&song.repeat_end.to_le_bytes());
+        for ch in &song.channels {
+            hash.write_channel(ch);
+        }
+        hash.0
+    }
+}
+
+/// 64 bit FNV-1a hash
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn write_channel(&mut self, ch: &Channel) {
+        self.write(&[ch.instrument, u8::from(ch.pizzicato)]);
+        self.write(&ch.finetune.to_le_bytes());
+        self.write(&(ch.events.len() as u64).to_le_bytes());
+        for event in &ch.events {
+            self.write(&event.position.to_le_bytes());
+            self.write(&[event.pitch, event.length, event.volume, event.pan]);
+        }
+    }
+}
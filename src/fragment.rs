@@ -0,0 +1,193 @@
+use crate::{
+    OrgError,
+    song::{Channel, Song},
+};
+
+/// A range of a song, cut out to be pasted somewhere else
+///
+/// Event positions are relative to the start of the range.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fragment {
+    /// Length of the range in ticks
+    pub len: u32,
+    /// The channels of the range, or `None` for channels that were left out
+    ///
+    /// Each channel keeps its instrument, finetune and pizzicato setting.
+    pub channels: [Option<Channel>; 16],
+}
+
+/// What to do when pasted events come from a channel with other instrument settings
+///
+/// Settings are the instrument, finetune and pizzicato. A channel without events has no
+/// conflicts, it takes the settings of the pasted channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InstrumentConflict {
+    /// Keep the settings of the target channel, so pasted events play with its instrument
+    #[default]
+    KeepTarget,
+    /// Replace the settings of the target channel, which changes how its other events sound
+    UseSource,
+    /// Move pasted events to another channel of the same kind that has the same settings, or no
+    /// events at all
+    Relocate,
+}
+
+/// Range editing
+///
+/// Ranges are given in ticks, from `start` included to `end` excluded. Notes that start in the
+/// range but sustain past its end are shortened to fit.
+impl Song {
+    /// Copy the events of `channels` between `start` and `end`
+    ///
+    /// # Panics
+    ///
+    /// - If a channel index is not below 16
+    #[must_use]
+    pub fn extract(&self, start: u32, end: u32, channels: &[usize]) -> Fragment {
+        let len = end.saturating_sub(start);
+        let mut fragment = Fragment {
+            len,
+            channels: Default::default(),
+        };
+        for &index in channels {
+            let ch = &self.channels[index];
+            let events = ch
+                .events
+                .iter()
+                .filter(|e| (start..end).contains(&e.position))
+                .map(|e| {
+                    let mut event = *e;
+                    event.position -= start;
+                    let room = len - event.position;
+                    if u32::from(event.length) > room {
+                        // Less than the length, so it fits
+                        event.length = u8::try_from(room).unwrap_or(u8::MAX);
+                    }
+                    event
+                })
+                .collect();
+            fragment.channels[index] = Some(Channel {
+                instrument: ch.instrument,
+                finetune: ch.finetune,
+                pizzicato: ch.pizzicato,
+                events,
+            });
+        }
+        fragment
+    }
+    /// A new song with every channel between `start` and `end`
+    ///
+    /// The repeat points are moved along, and kept in the range.
+    #[must_use]
+    pub fn slice(&self, start: u32, end: u32) -> Self {
+        let fragment = self.extract(start, end, &(0..16).collect::<Vec<_>>());
+        let len = fragment.len.max(1);
+        let repeat_start = self.repeat_start.saturating_sub(start).min(len - 1);
+        Self {
+            tempo_ms: self.tempo_ms,
+            repeat_start,
+            repeat_end: self
+                .repeat_end
+                .saturating_sub(start)
+                .clamp(repeat_start + 1, len),
+            channels: fragment.channels.map(Option::unwrap_or_default),
+            beats_per_measure: self.beats_per_measure,
+            steps_per_beat: self.steps_per_beat,
+        }
+    }
+    /// Paste `fragment` at `at`, replacing the events already in its range
+    ///
+    /// Each channel of the fragment goes to the channel with the same index, unless `conflict`
+    /// relocates it. Channels of the fragment without events only clear the range, and are left
+    /// out when relocating.
+    ///
+    /// # Errors
+    ///
+    /// With [`InstrumentConflict::Relocate`], returns [`OrgError::NoFreeChannel`] if a channel
+    /// has nowhere to go. The song is left unchanged.
+    pub fn paste(
+        &mut self,
+        at: u32,
+        fragment: &Fragment,
+        conflict: InstrumentConflict,
+    ) -> Result<(), OrgError> {
+        let targets = self.paste_targets(fragment, conflict)?;
+        let end = at.saturating_add(fragment.len);
+        for (source, target) in fragment.channels.iter().zip(targets) {
+            let (Some(source), Some(target)) = (source, target) else {
+                continue;
+            };
+            let ch = &mut self.channels[target];
+            if !source.events.is_empty()
+                && (ch.events.is_empty() || conflict != InstrumentConflict::KeepTarget)
+            {
+                ch.instrument = source.instrument;
+                ch.finetune = source.finetune;
+                ch.pizzicato = source.pizzicato;
+            }
+            ch.events.retain(|e| !(at..end).contains(&e.position));
+            for event in &source.events {
+                let mut event = *event;
+                event.position = event.position.saturating_add(at);
+                ch.insert_event(event);
+            }
+        }
+        Ok(())
+    }
+    /// Append `other` to the end of this song
+    ///
+    /// The events of `other` up to its repeat end are pasted at the repeat end of this song,
+    /// which then ends where `other` ends. Events of `other` keep their ticks, so both songs
+    /// should have the same tempo. [`Song::scale_tempo`] can match them beforehand.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::paste`].
+    pub fn concat(&mut self, other: &Self, conflict: InstrumentConflict) -> Result<(), OrgError> {
+        let fragment = other.extract(0, other.repeat_end, &(0..16).collect::<Vec<_>>());
+        let at = self.repeat_end;
+        self.paste(at, &fragment, conflict)?;
+        self.repeat_end = at.saturating_add(fragment.len);
+        Ok(())
+    }
+    /// The channel each channel of `fragment` is pasted to
+    fn paste_targets(
+        &self,
+        fragment: &Fragment,
+        conflict: InstrumentConflict,
+    ) -> Result<[Option<usize>; 16], OrgError> {
+        let mut targets = [None; 16];
+        if conflict != InstrumentConflict::Relocate {
+            for (i, target) in targets.iter_mut().enumerate() {
+                *target = fragment.channels[i].as_ref().map(|_| i);
+            }
+            return Ok(targets);
+        }
+        let mut taken = [false; 16];
+        for (i, source) in fragment.channels.iter().enumerate() {
+            // Channels without events have nothing to relocate
+            let Some(source) = source.as_ref().filter(|s| !s.events.is_empty()) else {
+                continue;
+            };
+            let group = if i < 8 { 0..8 } else { 8..16 };
+            // The channel with the same index first, then the others in order
+            let candidates = || std::iter::once(i).chain(group.clone().filter(move |&j| j != i));
+            let same = |j: usize| {
+                let ch = &self.channels[j];
+                (ch.instrument, ch.finetune, ch.pizzicato)
+                    == (source.instrument, source.finetune, source.pizzicato)
+            };
+            let fits = |j: usize| !taken[j] && (same(j) || self.channels[j].events.is_empty());
+            // Prefer channels that already use the same settings over empty ones
+            let target = candidates()
+                .find(|&j| fits(j) && (j == i || same(j)))
+                .or_else(|| candidates().find(|&j| fits(j)));
+            let Some(target) = target else {
+                return Err(OrgError::NoFreeChannel { channel: i });
+            };
+            taken[target] = true;
+            targets[i] = Some(target);
+        }
+        Ok(targets)
+    }
+}
@@ -0,0 +1,274 @@
+use {
+    crate::{PROPERTY_UNUSED, player::volume_db, song::Song},
+    std::{collections::BTreeMap, fmt},
+};
+
+/// Names of the pitch classes, starting from C
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Krumhansl-Kessler profile of major keys, starting from the tonic
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// Krumhansl-Kessler profile of minor keys, starting from the tonic
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Weight of each pitch class, starting from C
+type Profile = [f64; 12];
+
+/// Major or minor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyMode {
+    /// Major key
+    Major,
+    /// Minor key
+    Minor,
+}
+
+/// The estimated key of a song
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+    /// Pitch class of the tonic, from 0 for C to 11 for B
+    pub tonic: u8,
+    /// Major or minor
+    pub mode: KeyMode,
+    /// How well the notes match the key, from -1 to 1
+    pub correlation: f64,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            KeyMode::Major => "major",
+            KeyMode::Minor => "minor",
+        };
+        write!(f, "{} {mode}", NOTE_NAMES[usize::from(self.tonic)])
+    }
+}
+
+/// The kind of a chord
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChordQuality {
+    /// Major triad
+    Major,
+    /// Minor triad
+    Minor,
+    /// Diminished triad
+    Diminished,
+    /// Augmented triad
+    Augmented,
+    /// Major triad with a minor seventh
+    Dominant7,
+    /// Major triad with a major seventh
+    Major7,
+    /// Minor triad with a minor seventh
+    Minor7,
+}
+
+impl ChordQuality {
+    const ALL: [Self; 7] = [
+        Self::Major,
+        Self::Minor,
+        Self::Diminished,
+        Self::Augmented,
+        Self::Dominant7,
+        Self::Major7,
+        Self::Minor7,
+    ];
+    /// Intervals of the chord tones from the root, in semitones
+    const fn intervals(self) -> &'static [u8] {
+        match self {
+            Self::Major => &[0, 4, 7],
+            Self::Minor => &[0, 3, 7],
+            Self::Diminished => &[0, 3, 6],
+            Self::Augmented => &[0, 4, 8],
+            Self::Dominant7 => &[0, 4, 7, 10],
+            Self::Major7 => &[0, 4, 7, 11],
+            Self::Minor7 => &[0, 3, 7, 10],
+        }
+    }
+    const fn suffix(self) -> &'static str {
+        match self {
+            Self::Major => "",
+            Self::Minor => "m",
+            Self::Diminished => "dim",
+            Self::Augmented => "aug",
+            Self::Dominant7 => "7",
+            Self::Major7 => "maj7",
+            Self::Minor7 => "m7",
+        }
+    }
+}
+
+/// The estimated chord of a measure
+///
+/// Displays as a chord symbol, like `Am` or `G7`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    /// Pitch class of the root, from 0 for C to 11 for B
+    pub root: u8,
+    /// The kind of chord
+    pub quality: ChordQuality,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            NOTE_NAMES[usize::from(self.root)],
+            self.quality.suffix()
+        )
+    }
+}
+
+/// Harmonic analysis of a song, made by [`Song::harmony`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Harmony {
+    /// The key of the song, if it has notes
+    pub key: Option<Key>,
+    /// The chord of each measure with notes, with the index of the measure, sorted
+    pub chords: Vec<(u32, Chord)>,
+}
+
+impl Song {
+    /// Estimate the key of the song, and the chord of each measure
+    ///
+    /// Notes of the melody channels before the repeat end are weighted by how long they sound
+    /// and by their volume. A note sounds until its length runs out or the next note of its
+    /// channel starts. Measures are [`Self::measure_len`] ticks long.
+    ///
+    /// The key is the one whose Krumhansl-Kessler profile correlates best with the weights of
+    /// the whole song. The chord of a measure is the one whose tones match its weights best.
+    #[must_use]
+    pub fn harmony(&self) -> Harmony {
+        let measures = self.measure_profiles();
+        let mut total = [0.0; 12];
+        for profile in measures.values() {
+            for (sum, weight) in total.iter_mut().zip(profile) {
+                *sum += weight;
+            }
+        }
+        Harmony {
+            key: estimate_key(&total),
+            chords: measures
+                .iter()
+                .filter_map(|(&measure, profile)| Some((measure, estimate_chord(profile)?)))
+                .collect(),
+        }
+    }
+    /// Weight of each pitch class in each measure that has notes
+    fn measure_profiles(&self) -> BTreeMap<u32, Profile> {
+        let measure_len = self.measure_len();
+        let mut measures: BTreeMap<u32, Profile> = BTreeMap::new();
+        for ch in &self.channels[..8] {
+            let events: Vec<_> = ch
+                .events
+                .iter()
+                .filter(|e| e.position < self.repeat_end)
+                .collect();
+            // The player starts channels at this volume
+            let mut volume = 200;
+            for (i, event) in events.iter().enumerate() {
+                if event.volume != PROPERTY_UNUSED {
+                    volume = event.volume;
+                }
+                if event.pitch == PROPERTY_UNUSED {
+                    continue;
+                }
+                let next = events[i + 1..]
+                    .iter()
+                    .find(|e| e.pitch != PROPERTY_UNUSED)
+                    .map_or(self.repeat_end, |e| e.position);
+                let end = event.position.saturating_add(event.length.into()).min(next);
+                let amplitude = 10f64.powf(f64::from(volume_db(volume)) / 2000.0);
+                let pitch_class = usize::from(event.pitch % 12);
+                let mut start = event.position;
+                while start < end {
+                    let measure = start / measure_len;
+                    let measure_end = measure
+                        .saturating_add(1)
+                        .saturating_mul(measure_len)
+                        .min(end);
+                    measures.entry(measure).or_default()[pitch_class] +=
+                        f64::from(measure_end - start) * amplitude;
+                    start = measure_end;
+                }
+            }
+        }
+        measures
+    }
+}
+
+/// The key whose profile correlates best with `profile`
+fn estimate_key(profile: &Profile) -> Option<Key> {
+    let mut best: Option<Key> = None;
+    for (mode, key_profile) in [
+        (KeyMode::Major, &MAJOR_PROFILE),
+        (KeyMode::Minor, &MINOR_PROFILE),
+    ] {
+        for tonic in 0..12 {
+            let rotated: Profile = std::array::from_fn(|i| key_profile[(i + 12 - tonic) % 12]);
+            let Some(correlation) = correlation(profile, &rotated) else {
+                continue;
+            };
+            if best.is_none_or(|best| correlation > best.correlation) {
+                best = Some(Key {
+                    // Below 12
+                    tonic: u8::try_from(tonic).unwrap_or(0),
+                    mode,
+                    correlation,
+                });
+            }
+        }
+    }
+    best
+}
+
+/// The chord whose tones are closest to `profile`, by cosine similarity
+fn estimate_chord(profile: &Profile) -> Option<Chord> {
+    let norm = profile.iter().map(|w| w * w).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        return None;
+    }
+    let mut best = None;
+    let mut best_score = 0.0;
+    for root in 0..12u8 {
+        for quality in ChordQuality::ALL {
+            let intervals = quality.intervals();
+            let dot: f64 = intervals
+                .iter()
+                .map(|interval| profile[usize::from((root + interval) % 12)])
+                .sum();
+            // Lengths are small
+            #[expect(clippy::cast_precision_loss)]
+            let score = dot / (norm * (intervals.len() as f64).sqrt());
+            if score > best_score {
+                best_score = score;
+                best = Some(Chord { root, quality });
+            }
+        }
+    }
+    best
+}
+
+/// Pearson correlation of two profiles, if neither is flat
+fn correlation(a: &Profile, b: &Profile) -> Option<f64> {
+    let mean = |p: &Profile| p.iter().sum::<f64>() / 12.0;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let mut covariance = 0.0;
+    let mut variance_a = 0.0;
+    let mut variance_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        let (dx, dy) = (x - mean_a, y - mean_b);
+        covariance = dx.mul_add(dy, covariance);
+        variance_a = dx.mul_add(dx, variance_a);
+        variance_b = dy.mul_add(dy, variance_b);
+    }
+    let denominator = (variance_a * variance_b).sqrt();
+    (denominator > 0.0).then(|| covariance / denominator)
+}
@@ -0,0 +1,293 @@
+use crate::song::{Event, Song};
+
+/// A reversible change to a [`Song`]
+///
+/// Applying a command returns the command that reverts it, which is what [`History`] records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Replace the event at `position` of a channel, or remove it with `None`
+    ///
+    /// The position of `event` is ignored. Events that change nothing are removed, so the
+    /// channel stays normalized.
+    SetEvent {
+        /// Index of the channel
+        channel: usize,
+        /// Position of the event
+        position: u32,
+        /// The new event
+        event: Option<Event>,
+    },
+    /// Change the instrument of a channel
+    SetInstrument {
+        /// Index of the channel
+        channel: usize,
+        /// The new instrument
+        instrument: u8,
+    },
+    /// Change the finetune of a channel
+    SetFinetune {
+        /// Index of the channel
+        channel: usize,
+        /// The new finetune
+        finetune: u16,
+    },
+    /// Change whether the notes of a channel are played pizzicato
+    SetPizzicato {
+        /// Index of the channel
+        channel: usize,
+        /// Whether notes are pizzicato
+        pizzicato: bool,
+    },
+    /// Change the tempo of the song
+    SetTempo(u16),
+    /// Change the repeat points of the song
+    SetRepeat {
+        /// The point at which the song starts repeating
+        start: u32,
+        /// The point at which the song ends
+        end: u32,
+    },
+}
+
+impl Command {
+    /// Apply the command to `song`, and return the command that reverts it
+    ///
+    /// # Panics
+    ///
+    /// - If the channel index is not below 16
+    #[must_use = "the reverting command is the only way to undo the change"]
+    pub fn apply(&self, song: &mut Song) -> Self {
+        match *self {
+            Self::SetEvent {
+                channel,
+                position,
+                event,
+            } => {
+                let ch = &mut song.channels[channel];
+                let old = ch.remove_event(position);
+                if let Some(mut event) = event
+                    && !event.is_empty()
+                {
+                    event.position = position;
+                    ch.insert_event(event);
+                }
+                Self::SetEvent {
+                    channel,
+                    position,
+                    event: old,
+                }
+            }
+            Self::SetInstrument {
+                channel,
+                instrument,
+            } => Self::SetInstrument {
+                channel,
+                instrument: std::mem::replace(&mut song.channels[channel].instrument, instrument),
+            },
+            Self::SetFinetune { channel, finetune } => Self::SetFinetune {
+                channel,
+                finetune: std::mem::replace(&mut song.channels[channel].finetune, finetune),
+            },
+            Self::SetPizzicato { channel, pizzicato } => Self::SetPizzicato {
+                channel,
+                pizzicato: std::mem::replace(&mut song.channels[channel].pizzicato, pizzicato),
+            },
+            Self::SetTempo(tempo_ms) => {
+                Self::SetTempo(std::mem::replace(&mut song.tempo_ms, tempo_ms))
+            }
+            Self::SetRepeat { start, end } => Self::SetRepeat {
+                start: std::mem::replace(&mut song.repeat_start, start),
+                end: std::mem::replace(&mut song.repeat_end, end),
+            },
+        }
+    }
+}
+
+/// Default memory limit of a [`History`], in bytes
+pub const DEFAULT_HISTORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Undo and redo history of the changes made to a [`Song`]
+///
+/// Changes are made by applying [`Command`]s through the history. Commands applied between
+/// [`Self::begin`] and [`Self::commit`] form a transaction, which is undone and redone as a
+/// whole. When the recorded steps take more memory than the limit, the oldest ones are dropped.
+#[derive(Debug)]
+pub struct History {
+    /// Steps that can be undone, oldest first. Each one holds the commands reverting it.
+    undo: Vec<Vec<Command>>,
+    /// Steps that can be redone, most recently undone last
+    redo: Vec<Vec<Command>>,
+    /// Reverting commands of the open transaction
+    transaction: Vec<Command>,
+    /// Nesting depth of the open transaction
+    depth: usize,
+    memory_limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    /// An empty history that keeps about `memory_limit` bytes of steps
+    ///
+    /// The most recent step is always kept, even if it is larger than the limit.
+    #[must_use]
+    pub const fn new(memory_limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            transaction: Vec::new(),
+            depth: 0,
+            memory_limit,
+        }
+    }
+    /// Apply `command` to `song`, and record it
+    ///
+    /// This clears the steps that could be redone.
+    ///
+    /// # Panics
+    ///
+    /// - If the command refers to a channel index that is not below 16
+    pub fn apply(&mut self, song: &mut Song, command: &Command) {
+        let inverse = command.apply(song);
+        self.redo.clear();
+        self.transaction.push(inverse);
+        if self.depth == 0 {
+            self.push_step();
+        }
+    }
+    /// Start a transaction
+    ///
+    /// Transactions can be nested, only the outermost one forms a step.
+    pub const fn begin(&mut self) {
+        self.depth += 1;
+    }
+    /// End the transaction started by the last [`Self::begin`]
+    ///
+    /// Does nothing if no transaction is open.
+    pub fn commit(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.push_step();
+        }
+    }
+    /// Revert every command of the open transaction, and close it
+    ///
+    /// This closes nested transactions too. Does nothing if no transaction is open.
+    pub fn rollback(&mut self, song: &mut Song) {
+        for command in self.transaction.drain(..).rev() {
+            let _ = command.apply(song);
+        }
+        self.depth = 0;
+    }
+    /// Whether a transaction is open
+    #[must_use]
+    pub const fn in_transaction(&self) -> bool {
+        self.depth > 0
+    }
+    /// Revert the last step
+    ///
+    /// An open transaction is committed first. Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self, song: &mut Song) -> bool {
+        self.depth = 0;
+        self.push_step();
+        let Some(step) = self.undo.pop() else {
+            return false;
+        };
+        let redo = Self::revert(song, &step);
+        self.redo.push(redo);
+        true
+    }
+    /// Apply again the last undone step
+    ///
+    /// Returns `false` if there is nothing to redo, or if a transaction is open.
+    pub fn redo(&mut self, song: &mut Song) -> bool {
+        if self.in_transaction() {
+            return false;
+        }
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+        let undo = Self::revert(song, &step);
+        self.undo.push(undo);
+        true
+    }
+    /// Whether there is a step to undo
+    #[must_use]
+    pub const fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.transaction.is_empty()
+    }
+    /// Whether there is a step to redo
+    #[must_use]
+    pub const fn can_redo(&self) -> bool {
+        !self.in_transaction() && !self.redo.is_empty()
+    }
+    /// Forget every step, without changing the song
+    ///
+    /// An open transaction stays open, but its commands can't be rolled back anymore.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.transaction.clear();
+    }
+    /// The memory limit, in bytes
+    #[must_use]
+    pub const fn memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+    /// Change the memory limit, dropping the oldest steps if needed
+    pub fn set_memory_limit(&mut self, memory_limit: usize) {
+        self.memory_limit = memory_limit;
+        self.trim();
+    }
+    /// Approximate memory taken by the recorded steps, in bytes
+    #[must_use]
+    pub fn memory_used(&self) -> usize {
+        self.undo
+            .iter()
+            .chain(&self.redo)
+            .chain([&self.transaction])
+            .map(|step| step_size(step))
+            .sum()
+    }
+    /// Apply the commands of a step in reverse order, and return the step reverting that
+    fn revert(song: &mut Song, step: &[Command]) -> Vec<Command> {
+        step.iter()
+            .rev()
+            .map(|command| command.apply(song))
+            .collect()
+    }
+    /// Record the open transaction as an undo step
+    fn push_step(&mut self) {
+        if self.transaction.is_empty() {
+            return;
+        }
+        let step = std::mem::take(&mut self.transaction);
+        self.undo.push(step);
+        self.trim();
+    }
+    /// Drop the oldest steps until the memory used fits the limit, keeping the latest step
+    fn trim(&mut self) {
+        let mut used = self.memory_used();
+        // The redo steps furthest from the current state go first
+        while used > self.memory_limit && !self.redo.is_empty() {
+            used -= step_size(&self.redo.remove(0));
+        }
+        let mut dropped = 0;
+        while used > self.memory_limit && dropped + 1 < self.undo.len() {
+            used -= step_size(&self.undo[dropped]);
+            dropped += 1;
+        }
+        self.undo.drain(..dropped);
+    }
+}
+
+const fn step_size(step: &[Command]) -> usize {
+    size_of::<Vec<Command>>() + size_of_val(step)
+}
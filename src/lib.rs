@@ -1,6 +1,8 @@
 //! Library for rendering Organya music files (Cave Story & friends)
 //!
 //! Based on the [organya.h](<https://github.com/Strultz/organya.h>) project.
+//!
+//! The `serde` feature implements `Serialize` and `Deserialize` for [`Song`] and its parts.
 
 #![forbid(unsafe_code)]
 #![warn(
@@ -15,14 +17,59 @@
 )]
 #![allow(clippy::missing_errors_doc)]
 
+mod arrange;
+mod diff;
+mod edit;
+mod exe;
+mod fingerprint;
+mod fragment;
+mod harmony;
+mod history;
+mod lint;
+mod midi;
+mod mml;
+mod pixtone;
+mod piyo;
 mod player;
 mod read_cursor;
+mod render;
 mod song;
 mod sound;
+mod soundbank;
+mod stats;
+#[cfg(test)]
+mod test_util;
+mod text;
+mod timing;
+mod transform;
+mod view;
+mod wav;
+mod xm;
 
 pub use {
+    diff::{SongChange, SongDiff},
+    exe::ExeResources,
+    fragment::{Fragment, InstrumentConflict},
+    harmony::{Chord, ChordQuality, Harmony, Key, KeyMode},
+    history::{Command, DEFAULT_HISTORY_LIMIT, History},
+    lint::{Lint, LintKind},
+    midi::{GM_DRUM_MAP, MidiExportOptions, MidiImportOptions, Polyphony},
+    pixtone::{
+        PIXTONE_CHANNEL_COUNT, PIXTONE_SAMPLE_RATE, PixTone, PixToneChannel, PixToneOscillator,
+        PixToneWaveform,
+    },
+    piyo::{PIYO_ENVELOPE_LEN, PIYO_TRACK_COUNT, PiyoSong, PiyoTrack},
     player::Player,
+    render::LoopRender,
     song::{Channel, Event, Song},
+    soundbank::{
+        CLASSIC_DRUM_COUNT, MELODY_WAVE_COUNT, MELODY_WAVE_LEN, SampleFormat, Samples, Soundbank,
+    },
+    stats::{ChannelStats, SongStats},
+    timing::{MusicalTime, Timing},
+    transform::{PitchOverflow, TempoScale},
+    view::{ChannelView, Events, SongView},
+    wav::{WavFormat, WavOptions},
 };
 
 /// How to interpolate samples
@@ -40,6 +87,33 @@ pub enum Interpolation {
 pub enum OrgError {
     /// Malformed Organya file
     Malformed,
+    /// Malformed PE executable
+    MalformedExe,
+    /// Malformed or unsupported MIDI file
+    MalformedMidi,
+    /// Malformed PiyoPiyo file
+    MalformedPiyo,
+    /// Malformed PixTone parameters
+    MalformedPixTone,
+    /// Syntax error in a text format
+    Syntax(SyntaxError),
+    /// No channel is free to relocate the events of a pasted channel to
+    NoFreeChannel {
+        /// Index of the pasted channel
+        channel: usize,
+    },
+    /// A change of a patch doesn't apply to the song
+    PatchConflict {
+        /// Index of the change in the patch
+        change: usize,
+    },
+    /// A note would end up with a pitch outside of `0..96`
+    PitchOutOfRange {
+        /// Index of the channel of the note
+        channel: usize,
+        /// Position of the note
+        position: u32,
+    },
     /// Input/Output error
     Io(std::io::Error),
 }
@@ -54,6 +128,23 @@ impl std::fmt::Display for OrgError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             OrgError::Malformed => f.write_str("malformed Organya file"),
+            OrgError::MalformedExe => f.write_str("malformed PE executable"),
+            OrgError::MalformedMidi => f.write_str("malformed or unsupported MIDI file"),
+            OrgError::MalformedPiyo => f.write_str("malformed PiyoPiyo file"),
+            OrgError::MalformedPixTone => f.write_str("malformed PixTone parameters"),
+            OrgError::Syntax(error) => error.fmt(f),
+            OrgError::NoFreeChannel { channel } => {
+                write!(f, "no free channel to paste channel {channel} to")
+            }
+            OrgError::PatchConflict { change } => {
+                write!(f, "change {change} of the patch doesn't apply to the song")
+            }
+            OrgError::PitchOutOfRange { channel, position } => {
+                write!(
+                    f,
+                    "pitch out of range on channel {channel} at position {position}"
+                )
+            }
             OrgError::Io(error) => error.fmt(f),
         }
     }
@@ -61,5 +152,22 @@ impl std::fmt::Display for OrgError {
 
 impl std::error::Error for OrgError {}
 
+/// Location and description of an error in a text format
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    /// Line of the error, starting from 1
+    pub line: usize,
+    /// Column of the error in characters, starting from 1
+    pub column: usize,
+    /// What is wrong
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
 /// If a property (volume, pan, etc.) has this value, it is ignored
 pub const PROPERTY_UNUSED: u8 = 0xFF;
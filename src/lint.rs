@@ -0,0 +1,442 @@
+use crate::{
+    PROPERTY_UNUSED,
+    player::{PANNING_TABLE, pan_db, volume_db},
+    song::{Channel, Event, Song},
+};
+
+/// A musically meaningless pattern found by [`Song::lint`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// Index of the channel
+    pub channel: usize,
+    /// Position of the event
+    pub position: u32,
+    /// What is wrong with the event
+    pub kind: LintKind,
+}
+
+/// What a [`Lint`] is about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintKind {
+    /// The events of the channel are not sorted by position, or several share a position
+    ///
+    /// The player can miss events of such channels, and nothing else is checked on them. This has
+    /// no fix that keeps the audio, [`Channel::normalize`] is the closest.
+    Unsorted,
+    /// The event is never played, because it's at or after the repeat end
+    AfterRepeatEnd,
+    /// The event doesn't play a note, nor change the volume or the pan
+    EmptyEvent,
+    /// The note lasts past the start of the next note, which cuts it
+    OverlappingNote {
+        /// Position of the next note
+        next: u32,
+    },
+    /// The volume change sets the volume the sound already has, after its volume ramp ended
+    RedundantVolume,
+    /// The pan change sets the pan the sound already has, after its volume ramp ended
+    RedundantPan,
+    /// The volume change happens while no note is playing, so the player ignores it
+    IdleVolume,
+    /// The pan change happens while no note is playing, so the player ignores it
+    IdlePan,
+}
+
+/// Maximum number of loops simulated before giving up on finding redundant changes
+const MAX_LOOPS: usize = 64;
+/// Shortest tempo whose ticks outlast a volume ramp, which takes 1/250th of a second
+const RAMP_TEMPO_MS: u16 = 4;
+
+/// Linting
+///
+/// Every lint except [`LintKind::Unsorted`] has a fix that leaves the output of the [`Player`]
+/// unchanged, when it plays the song from the beginning with no channel muted. Below a sample
+/// rate of 32000 Hz, a volume ramp can end a rounding error away from its target, so removing
+/// a redundant change can alter the output by as much.
+///
+/// [`Player`]: crate::Player
+impl Song {
+    /// Find the musically meaningless events of the song
+    ///
+    /// Lints are sorted by channel, then position. An event can have several lints, like a
+    /// redundant volume and an idle pan.
+    #[must_use]
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        for (i, ch) in self.channels.iter().enumerate() {
+            if !ch.is_normalized() {
+                lints.push(Lint {
+                    channel: i,
+                    position: ch.events.first().map_or(0, |e| e.position),
+                    kind: LintKind::Unsorted,
+                });
+                continue;
+            }
+            let usage = self.usage(ch, i < 8);
+            for (event, usage) in ch.events.iter().zip(usage) {
+                let mut push = |kind| {
+                    lints.push(Lint {
+                        channel: i,
+                        position: event.position,
+                        kind,
+                    });
+                };
+                if !usage.played {
+                    push(LintKind::AfterRepeatEnd);
+                    continue;
+                }
+                if event.is_empty() {
+                    push(LintKind::EmptyEvent);
+                    continue;
+                }
+                if let Some(next) = usage.cut_at
+                    && next - event.position < u32::from(event.length)
+                {
+                    push(LintKind::OverlappingNote { next });
+                }
+                match usage.volume {
+                    Change::Unused | Change::Needed => {}
+                    Change::Redundant => push(LintKind::RedundantVolume),
+                    Change::Idle => push(LintKind::IdleVolume),
+                }
+                match usage.pan {
+                    Change::Unused | Change::Needed => {}
+                    Change::Redundant => push(LintKind::RedundantPan),
+                    Change::Idle => push(LintKind::IdlePan),
+                }
+            }
+        }
+        lints
+    }
+    /// Fix `lints` found by [`Self::lint`], by removing or shortening events
+    ///
+    /// Lints of the song as it currently is can be fixed in any combination, and the output of
+    /// the player stays the same. Lints that don't match the song are ignored.
+    pub fn fix_lints(&mut self, lints: &[Lint]) {
+        for lint in lints {
+            let Some(ch) = self.channels.get_mut(lint.channel) else {
+                continue;
+            };
+            if ch.event_at(lint.position).is_none() {
+                continue;
+            }
+            match lint.kind {
+                LintKind::Unsorted => {}
+                LintKind::AfterRepeatEnd | LintKind::EmptyEvent => {
+                    ch.remove_event(lint.position);
+                }
+                LintKind::OverlappingNote { next } => {
+                    if let Some(event) = ch.events.iter_mut().find(|e| e.position == lint.position)
+                        && let Ok(length) = u8::try_from(next.saturating_sub(lint.position))
+                        && length > 0
+                    {
+                        event.length = event.length.min(length);
+                    }
+                }
+                LintKind::RedundantVolume | LintKind::IdleVolume => {
+                    ch.set_volume(lint.position, PROPERTY_UNUSED);
+                }
+                LintKind::RedundantPan | LintKind::IdlePan => {
+                    ch.set_pan(lint.position, PROPERTY_UNUSED);
+                }
+            }
+        }
+    }
+    /// Simulate how the player uses each event of a channel, over every loop of the song
+    fn usage(&self, ch: &Channel, melody: bool) -> Vec<Usage> {
+        let mut usage = vec![Usage::default(); ch.events.len()];
+        let mut state = ChannelState {
+            ticks_end_ramps: self.tempo_ms >= RAMP_TEMPO_MS,
+            ..ChannelState::default()
+        };
+        // The first pass plays from the beginning, the next ones loop from the repeat start
+        let mut first = 0;
+        let mut loop_states = Vec::new();
+        for _ in 0..=MAX_LOOPS {
+            let last = self.repeat_end.max(first + 1);
+            state.play(ch, melody, first..last, &mut usage);
+            first = self.repeat_start;
+            if loop_states.contains(&state) {
+                return usage;
+            }
+            loop_states.push(state.clone());
+        }
+        // The loops don't settle, consider every change as needed
+        for usage in &mut usage {
+            usage.volume.merge(Change::Needed);
+            usage.pan.merge(Change::Needed);
+        }
+        usage
+    }
+}
+
+/// What a volume or pan change of an event does, over every time it's played
+///
+/// Ordered so that combining two uses keeps the one that matters most.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+enum Change {
+    /// There is no change, or the event is never played
+    #[default]
+    Unused,
+    /// No note is playing whenever the change happens
+    Idle,
+    /// The sound always has that value already
+    Redundant,
+    /// The change matters at least once
+    Needed,
+}
+
+impl Change {
+    /// Combine with what the change does another time it's played
+    fn merge(&mut self, other: Self) {
+        *self = (*self).max(other);
+    }
+}
+
+/// How the player uses an event
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    played: bool,
+    volume: Change,
+    pan: Change,
+    /// Position of the next note, if it cuts this note every time it's played
+    cut_at: Option<u32>,
+    /// Whether the note plays at least once without being cut by a next note
+    uncut: bool,
+}
+
+/// Volume and pan of a sound, in hundredths of a decibel, and its volume ramp
+///
+/// Sounds start at full volume and centered, which are both 0. Once a sound has played, volume
+/// and pan changes ramp to the new value. Setting any value restarts the ramp, so setting the
+/// value the sound already has only does nothing once the ramp ended.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct SoundState {
+    volume: i16,
+    pan: i16,
+    started: bool,
+    /// Whether the volume ramp may not have ended
+    ramping: bool,
+}
+
+/// The part of the player state of a channel that decides what events do
+#[derive(Clone, Default, PartialEq, Eq)]
+struct ChannelState {
+    pitch: Option<u8>,
+    alt: usize,
+    ticks: u32,
+    /// One sound for drums, two sounds per octave for melodies
+    sounds: [[SoundState; 2]; 8],
+    /// Whether a tick of a held note is long enough to end the volume ramp of its sound
+    ticks_end_ramps: bool,
+}
+
+impl ChannelState {
+    /// Play the events of one pass over `ticks`
+    fn play(
+        &mut self,
+        ch: &Channel,
+        melody: bool,
+        ticks: std::ops::Range<u32>,
+        usage: &mut [Usage],
+    ) {
+        let mut tick = ticks.start;
+        let played = ch
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| ticks.contains(&e.position));
+        for (index, event) in played {
+            if melody {
+                self.advance(event.position - tick, ch.pizzicato);
+            }
+            tick = event.position + 1;
+            let usage_of = &mut usage[index];
+            usage_of.played = true;
+            if melody {
+                self.play_melody(*event, ch, index, ticks.end, usage);
+                self.advance(1, ch.pizzicato);
+                // The held note loops its sound through the whole tick
+                if self.ticks_end_ramps
+                    && !ch.pizzicato
+                    && let Some(pitch) = self.pitch
+                {
+                    self.sounds[usize::from(pitch / 12)][self.alt].ramping = false;
+                }
+            } else {
+                self.play_drum(*event, usage_of);
+            }
+        }
+        if melody {
+            self.advance(ticks.end - tick, ch.pizzicato);
+        }
+    }
+    fn play_melody(
+        &mut self,
+        event: Event,
+        ch: &Channel,
+        index: usize,
+        end: u32,
+        usage: &mut [Usage],
+    ) {
+        if event.pitch != PROPERTY_UNUSED {
+            if self.pitch.is_some() {
+                self.alt ^= 1;
+            }
+            self.pitch = Some(event.pitch);
+            self.ticks = u32::from(event.length);
+            let next = ch.events[index + 1..]
+                .iter()
+                .find(|e| e.pitch != PROPERTY_UNUSED)
+                .map(|e| e.position)
+                .filter(|&position| position < end);
+            let usage = &mut usage[index];
+            match next {
+                Some(next) if !usage.uncut => usage.cut_at = Some(next),
+                _ => {
+                    usage.uncut = true;
+                    usage.cut_at = None;
+                }
+            }
+        }
+        let usage = &mut usage[index];
+        let sound = self
+            .pitch
+            .map(|pitch| &mut self.sounds[usize::from(pitch / 12)][self.alt]);
+        if let Some(sound) = sound {
+            Self::change(event, sound, usage);
+            sound.started |= event.pitch != PROPERTY_UNUSED;
+        } else {
+            if event.volume != PROPERTY_UNUSED {
+                usage.volume.merge(Change::Idle);
+            }
+            if event.pan != PROPERTY_UNUSED {
+                usage.pan.merge(Change::Idle);
+            }
+        }
+    }
+    /// Drums play until their sample ends, so their volume ramps are never known to end
+    fn play_drum(&mut self, event: Event, usage: &mut Usage) {
+        let sound = &mut self.sounds[0][0];
+        Self::change(event, sound, usage);
+        sound.started |= event.pitch != PROPERTY_UNUSED;
+    }
+    /// Apply the volume and pan changes of `event` to a sound
+    fn change(event: Event, sound: &mut SoundState, usage: &mut Usage) {
+        let settled = !sound.ramping;
+        if event.volume != PROPERTY_UNUSED {
+            let volume = volume_db(event.volume);
+            if sound.volume == volume && settled {
+                usage.volume.merge(Change::Redundant);
+            } else {
+                usage.volume.merge(Change::Needed);
+                sound.ramping = sound.started;
+            }
+            sound.volume = volume;
+        }
+        if event.pan != PROPERTY_UNUSED {
+            // The player can't play invalid pans, don't report anything about them
+            if usize::from(event.pan) >= PANNING_TABLE.len() {
+                usage.pan.merge(Change::Needed);
+                return;
+            }
+            let pan = pan_db(event.pan);
+            // A volume change right before restarts the ramp the same way
+            if sound.pan == pan && (settled || event.volume != PROPERTY_UNUSED) {
+                usage.pan.merge(Change::Redundant);
+            } else {
+                usage.pan.merge(Change::Needed);
+                sound.ramping = sound.started;
+            }
+            sound.pan = pan;
+        }
+    }
+    /// Let `ticks` ticks pass, stopping the note when its length runs out
+    const fn advance(&mut self, ticks: u32, pizzicato: bool) {
+        if ticks > self.ticks && !pizzicato {
+            self.pitch = None;
+        }
+        self.ticks = self.ticks.saturating_sub(ticks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::test_util::{random_song, render},
+    };
+
+    /// Enough frames to play a few loops of the random songs
+    const FRAMES: usize = 44_100 * 2;
+
+    #[test]
+    fn fixes_keep_output() {
+        let mut kinds = Vec::new();
+        for seed in 0..12 {
+            let song = random_song(seed);
+            let lints = song.lint();
+            kinds.extend(lints.iter().map(|l| std::mem::discriminant(&l.kind)));
+            let mut fixed = song.clone();
+            fixed.fix_lints(&lints);
+            assert_ne!(fixed, song, "seed {seed}");
+            assert!(
+                render(&song, FRAMES) == render(&fixed, FRAMES),
+                "seed {seed}"
+            );
+            // Every other lint on its own
+            let mut half = song.clone();
+            half.fix_lints(&lints.iter().copied().step_by(2).collect::<Vec<_>>());
+            assert!(
+                render(&song, FRAMES) == render(&half, FRAMES),
+                "seed {seed}"
+            );
+        }
+        kinds.sort_by_key(|k| format!("{k:?}"));
+        kinds.dedup();
+        // All the kinds except Unsorted
+        assert_eq!(kinds.len(), 7);
+    }
+
+    #[test]
+    fn change_during_ramp_is_needed() {
+        let note = |position, volume| Event {
+            position,
+            pitch: 48,
+            length: 20,
+            volume,
+            pan: PROPERTY_UNUSED,
+        };
+        let mut song = Song {
+            // Too short for a tick to end the volume ramp
+            tempo_ms: 2,
+            repeat_end: 32,
+            ..Song::default()
+        };
+        song.channels[0].events = vec![
+            note(0, 200),
+            Event {
+                pitch: PROPERTY_UNUSED,
+                ..note(1, 50)
+            },
+            Event {
+                pitch: PROPERTY_UNUSED,
+                ..note(2, 50)
+            },
+        ];
+        assert!(song.lint().is_empty());
+        let mut removed = song.clone();
+        removed.channels[0].set_volume(2, PROPERTY_UNUSED);
+        assert!(render(&song, 4410) != render(&removed, 4410));
+        // With a slower tempo, the ramp ends before the repeated volume
+        song.tempo_ms = 10;
+        assert_eq!(
+            song.lint(),
+            [Lint {
+                channel: 0,
+                position: 2,
+                kind: LintKind::RedundantVolume
+            }]
+        );
+    }
+}
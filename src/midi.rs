@@ -0,0 +1,750 @@
+use {
+    crate::{
+        OrgError, PROPERTY_UNUSED,
+        read_cursor::ReadCursor,
+        song::{Event, Song},
+    },
+    std::collections::BTreeMap,
+};
+
+/// General MIDI percussion notes for the drums of the original Organya soundbank
+pub static GM_DRUM_MAP: [u8; 42] = [
+    36, // Bass01 => Bass Drum 1
+    35, // Bass02 => Acoustic Bass Drum
+    38, // Snare01 => Acoustic Snare
+    40, // Snare02 => Electric Snare
+    45, // Tom01 => Low Tom
+    42, // HiClose => Closed Hi-Hat
+    46, // HiOpen => Open Hi-Hat
+    49, // Crash => Crash Cymbal 1
+    63, // Per01 => Open Hi Conga
+    64, // Per02 => Low Conga
+    36, // Bass03 => Bass Drum 1
+    47, // Tom02 => Low-Mid Tom
+    35, // Bass04 => Acoustic Bass Drum
+    36, // Bass05 => Bass Drum 1
+    38, // Snare03 => Acoustic Snare
+    40, // Snare04 => Electric Snare
+    42, // HiClose02 => Closed Hi-Hat
+    46, // HiOpen02 => Open Hi-Hat
+    42, // HiClose03 => Closed Hi-Hat
+    46, // HiOpen03 => Open Hi-Hat
+    57, // Crash02 => Crash Cymbal 2
+    55, // RevSym01 => Splash Cymbal
+    51, // Ride01 => Ride Cymbal 1
+    48, // Tom03 => Hi-Mid Tom
+    50, // Tom04 => High Tom
+    41, // OrcDrm => Low Floor Tom
+    53, // Bell => Ride Bell
+    56, // Cat => Cowbell
+    35, // Bass06 => Acoustic Bass Drum
+    36, // Bass07 => Bass Drum 1
+    38, // Snare05 => Acoustic Snare
+    40, // Snare06 => Electric Snare
+    37, // Snare07 => Side Stick
+    43, // Tom05 => High Floor Tom
+    46, // HiOpen04 => Open Hi-Hat
+    44, // HiClose04 => Pedal Hi-Hat
+    39, // Clap01 => Hand Clap
+    54, // Pesi01 => Tambourine
+    70, // Quick01 => Maracas
+    35, // Bass08 => Acoustic Bass Drum
+    38, // Snare08 => Acoustic Snare
+    42, // HiClose05 => Closed Hi-Hat
+];
+
+/// The General MIDI percussion note used for drums missing from the drum map
+const FALLBACK_DRUM_NOTE: u8 = 38;
+/// The General MIDI percussion channel
+const PERCUSSION_CHANNEL: u8 = 9;
+/// MIDI note number of Organya pitch 0
+const PITCH_OFFSET: u8 = 24;
+/// The volume of a channel before any volume event
+const DEFAULT_VOLUME: u8 = 200;
+/// Maximum value of Organya volume
+const MAX_VOLUME: u8 = 254;
+/// Maximum value of Organya pan
+const MAX_PAN: u8 = 12;
+
+/// Options for [`Song::to_midi`]
+#[derive(Clone)]
+pub struct MidiExportOptions {
+    /// General MIDI percussion note for each drum instrument, indexed by instrument
+    ///
+    /// Drums without an entry are mapped to the acoustic snare.
+    pub drum_map: Vec<u8>,
+}
+
+impl Default for MidiExportOptions {
+    fn default() -> Self {
+        Self {
+            drum_map: GM_DRUM_MAP.to_vec(),
+        }
+    }
+}
+
+/// How notes that overlap on the same MIDI channel are imported
+///
+/// Organya channels can only play one note at a time.
+#[derive(Clone, Copy, Default)]
+pub enum Polyphony {
+    /// A new note cuts off the one that is playing. Of notes starting together, the highest
+    /// one is kept.
+    #[default]
+    Latest,
+    /// While notes overlap, the highest one plays. Lower notes starting under a higher note
+    /// are dropped.
+    Highest,
+    /// Overlapping notes are spread over additional melody channels
+    Spill,
+}
+
+/// Options for [`Song::from_midi`]
+#[derive(Clone)]
+pub struct MidiImportOptions {
+    /// Steps per beat of the imported song. Notes are quantized to this grid.
+    pub steps_per_beat: u8,
+    /// How notes overlapping on the same MIDI channel are imported
+    pub polyphony: Polyphony,
+    /// Organya drum instrument for each General MIDI percussion note, indexed by note
+    ///
+    /// Percussion notes without an instrument are dropped.
+    pub drum_map: Vec<Option<u8>>,
+    /// The pitch of imported drum notes
+    pub drum_pitch: u8,
+}
+
+impl Default for MidiImportOptions {
+    fn default() -> Self {
+        let mut drum_map = vec![None; 128];
+        for (instrument, &note) in (0u8..).zip(&GM_DRUM_MAP) {
+            drum_map[usize::from(note)].get_or_insert(instrument);
+        }
+        Self {
+            steps_per_beat: 4,
+            polyphony: Polyphony::default(),
+            drum_map,
+            drum_pitch: 27,
+        }
+    }
+}
+
+/// A note read from a MIDI file, with times in MIDI ticks
+#[derive(Clone, Copy)]
+struct MidiNote {
+    start: u32,
+    end: u32,
+    key: u8,
+    velocity: u8,
+}
+
+/// The contents of a MIDI file relevant to Organya
+#[derive(Default)]
+struct MidiContents {
+    division: u16,
+    micros_per_beat: Option<u32>,
+    time_signature: Option<(u8, u8)>,
+    loop_start: Option<u32>,
+    loop_end: Option<u32>,
+    /// Melodic notes, grouped by track and MIDI channel
+    voices: BTreeMap<(usize, u8), Vec<MidiNote>>,
+    percussion: Vec<MidiNote>,
+    /// Pan changes for each MIDI channel
+    pans: [Vec<(u32, u8)>; 16],
+}
+
+impl MidiContents {
+    fn read(data: &[u8]) -> Option<Self> {
+        let mut read = ReadCursor(data);
+        if read.next_bytes()? != b"MThd" {
+            return None;
+        }
+        let header_len = u32::from_be_bytes(*read.next_bytes()?) as usize;
+        let mut header = ReadCursor(read.next_n_bytes(header_len)?);
+        let _format = u16::from_be_bytes(*header.next_bytes()?);
+        let track_count = u16::from_be_bytes(*header.next_bytes()?);
+        let division = u16::from_be_bytes(*header.next_bytes()?);
+        // SMPTE timing is not supported
+        if division & 0x8000 != 0 || division == 0 {
+            return None;
+        }
+        let mut this = Self {
+            division,
+            ..Self::default()
+        };
+        let mut track = 0;
+        while track < usize::from(track_count) {
+            let kind: [u8; 4] = *read.next_bytes()?;
+            let len = u32::from_be_bytes(*read.next_bytes()?) as usize;
+            let data = read.next_n_bytes(len)?;
+            // Unknown chunks are skipped
+            if &kind == b"MTrk" {
+                this.read_track(track, data)?;
+                track += 1;
+            }
+        }
+        for notes in this.voices.values_mut().chain([&mut this.percussion]) {
+            notes.sort_by_key(|n| (n.start, std::cmp::Reverse(n.key)));
+        }
+        for pans in &mut this.pans {
+            pans.sort_by_key(|&(tick, _)| tick);
+        }
+        Some(this)
+    }
+    fn read_track(&mut self, track: usize, data: &[u8]) -> Option<()> {
+        let mut read = ReadCursor(data);
+        let mut tick = 0u32;
+        let mut status = 0u8;
+        // Start tick and velocity of playing notes, indexed by channel and key
+        let mut playing: BTreeMap<(u8, u8), Vec<(u32, u8)>> = BTreeMap::new();
+        while !read.0.is_empty() {
+            tick = tick.checked_add(read_var_len(&mut read)?)?;
+            let mut byte = read.next_u8()?;
+            if byte >= 0x80 {
+                status = byte;
+                if status < 0xF0 {
+                    byte = read.next_u8()?;
+                }
+            }
+            let channel = status & 0x0F;
+            match status & 0xF0 {
+                // Running status without a previous status byte
+                0x00..0x80 => return None,
+                kind @ (0x80 | 0x90) => {
+                    let velocity = read.next_u8()?;
+                    if kind == 0x90 && velocity > 0 {
+                        playing
+                            .entry((channel, byte))
+                            .or_default()
+                            .push((tick, velocity));
+                    } else if let Some(notes) = playing.get_mut(&(channel, byte))
+                        && !notes.is_empty()
+                    {
+                        let (start, velocity) = notes.remove(0);
+                        self.push_note(
+                            track,
+                            channel,
+                            MidiNote {
+                                start,
+                                end: tick,
+                                key: byte,
+                                velocity,
+                            },
+                        );
+                    }
+                }
+                0xA0 | 0xE0 => {
+                    read.next_u8()?;
+                }
+                0xB0 => {
+                    let value = read.next_u8()?;
+                    // Pan
+                    if byte == 10 {
+                        self.pans[usize::from(channel)].push((tick, value));
+                    }
+                }
+                0xC0 | 0xD0 => {}
+                _ => match status {
+                    0xFF => {
+                        let kind = read.next_u8()?;
+                        let len = read_var_len(&mut read)? as usize;
+                        let data = read.next_n_bytes(len)?;
+                        self.read_meta(tick, kind, data);
+                        status = 0;
+                        // End of track
+                        if kind == 0x2F {
+                            break;
+                        }
+                    }
+                    0xF0 | 0xF7 => {
+                        // `byte` wasn't read for system messages
+                        let len = read_var_len(&mut read)? as usize;
+                        read.next_n_bytes(len)?;
+                        status = 0;
+                    }
+                    _ => return None,
+                },
+            }
+        }
+        // Notes that are never released end with the track
+        for ((channel, key), notes) in playing {
+            for (start, velocity) in notes {
+                self.push_note(
+                    track,
+                    channel,
+                    MidiNote {
+                        start,
+                        end: tick,
+                        key,
+                        velocity,
+                    },
+                );
+            }
+        }
+        Some(())
+    }
+    fn read_meta(&mut self, tick: u32, kind: u8, data: &[u8]) {
+        match (kind, data) {
+            (0x51, &[a, b, c]) => {
+                self.micros_per_beat
+                    .get_or_insert(u32::from_be_bytes([0, a, b, c]));
+            }
+            (0x58, &[numerator, denominator, ..]) => {
+                self.time_signature.get_or_insert((numerator, denominator));
+            }
+            (0x06, b"loopStart") => self.loop_start = Some(tick),
+            (0x06, b"loopEnd") => self.loop_end = Some(tick),
+            _ => {}
+        }
+    }
+    fn push_note(&mut self, track: usize, channel: u8, note: MidiNote) {
+        if channel == PERCUSSION_CHANNEL {
+            self.percussion.push(note);
+        } else {
+            self.voices.entry((track, channel)).or_default().push(note);
+        }
+    }
+    /// The pan of `channel` at `tick`, if it was set
+    fn pan_at(&self, channel: u8, tick: u32) -> Option<u8> {
+        let pans = &self.pans[usize::from(channel)];
+        let index = pans.partition_point(|&(t, _)| t <= tick);
+        index.checked_sub(1).map(|i| pans[i].1)
+    }
+}
+
+/// A note quantized to the Organya step grid
+#[derive(Clone, Copy)]
+struct Step {
+    position: u32,
+    length: u32,
+    pitch: u8,
+    velocity: u8,
+    pan: Option<u8>,
+}
+
+impl Step {
+    const fn end(&self) -> u32 {
+        self.position + self.length
+    }
+}
+
+/// Split quantized notes of a MIDI channel into monophonic lanes
+fn split_lanes(notes: &[Step], polyphony: Polyphony) -> Vec<Vec<Step>> {
+    let mut lanes: Vec<Vec<Step>> = Vec::new();
+    for &note in notes {
+        let lane = match polyphony {
+            // Notes are sorted highest first among notes starting together
+            Polyphony::Latest => match lanes.first() {
+                Some(lane)
+                    if lane
+                        .last()
+                        .is_some_and(|last| last.position == note.position) =>
+                {
+                    None
+                }
+                _ => Some(0),
+            },
+            Polyphony::Highest => match lanes.first().and_then(|lane| lane.last()) {
+                Some(last) if last.position == note.position => None,
+                Some(last) if last.end() > note.position && last.pitch > note.pitch => None,
+                _ => Some(0),
+            },
+            Polyphony::Spill => Some(
+                lanes
+                    .iter()
+                    .position(|lane| lane.last().is_none_or(|last| last.end() <= note.position))
+                    .unwrap_or(lanes.len()),
+            ),
+        };
+        if let Some(lane) = lane {
+            if lane == lanes.len() {
+                lanes.push(Vec::new());
+            }
+            lanes[lane].push(note);
+        }
+    }
+    lanes
+}
+
+/// A MIDI event at an absolute time
+struct TimedEvent {
+    tick: u32,
+    /// Events at the same tick are ordered by this
+    order: u8,
+    bytes: Vec<u8>,
+}
+
+impl Song {
+    /// Convert the song to a Type-1 Standard MIDI File
+    ///
+    /// The first track holds the tempo, the time signature, and `loopStart`/`loopEnd` markers
+    /// at the repeat points. It's followed by a track for each of the 16 channels. Melody
+    /// channels use MIDI channels 1-8, and drum channels use the percussion channel.
+    ///
+    /// A beat is a quarter note, and a MIDI tick is an Organya tick. Volume is mapped to note
+    /// velocity, and the pan of melody channels to CC10. Drum channels share the percussion
+    /// channel, so their pans would overwrite each other and are not exported. Events at or
+    /// after the repeat end are not exported.
+    #[must_use]
+    pub fn to_midi(&self, options: &MidiExportOptions) -> Vec<u8> {
+        let steps_per_beat = self.steps_per_beat.max(1);
+        let mut out = Vec::new();
+        out.extend_from_slice(b"MThd");
+        out.extend_from_slice(&6u32.to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(1 + 16u16).to_be_bytes());
+        out.extend_from_slice(&u16::from(steps_per_beat).to_be_bytes());
+
+        let micros_per_beat =
+            (u64::from(self.tempo_ms) * 1000 * u64::from(steps_per_beat)).min(0xFF_FFFF);
+        // At most 24 bits
+        let micros_per_beat = u32::try_from(micros_per_beat).unwrap_or(0xFF_FFFF);
+        let mut conductor = vec![
+            meta(0, 0x58, &[self.beats_per_measure, 2, 24, 8]),
+            meta(0, 0x51, &micros_per_beat.to_be_bytes()[1..]),
+        ];
+        if self.repeat_start < self.repeat_end {
+            conductor.push(meta(self.repeat_start, 0x06, b"loopStart"));
+            conductor.push(meta(self.repeat_end, 0x06, b"loopEnd"));
+        }
+        write_track(&mut out, conductor);
+
+        for (i, ch) in (0u8..).zip(&self.channels) {
+            let is_drum = i >= 8;
+            let midi_ch = if is_drum { PERCUSSION_CHANNEL } else { i };
+            let name = if is_drum {
+                format!("Drum {} (instrument {})", i - 7, ch.instrument)
+            } else {
+                format!("Melody {} (wave {})", i + 1, ch.instrument)
+            };
+            let mut events = vec![meta(0, 0x03, name.as_bytes())];
+            let mut volume = DEFAULT_VOLUME;
+            let playable = ch.events.iter().filter(|e| e.position < self.repeat_end);
+            let pitched: Vec<_> = playable
+                .clone()
+                .filter(|e| e.pitch != PROPERTY_UNUSED)
+                .map(|e| e.position)
+                .collect();
+            for evt in playable {
+                if evt.pan != PROPERTY_UNUSED && !is_drum {
+                    events.push(TimedEvent {
+                        tick: evt.position,
+                        order: 1,
+                        bytes: vec![0xB0 | midi_ch, 10, to_midi_range(evt.pan, MAX_PAN)],
+                    });
+                }
+                if evt.volume != PROPERTY_UNUSED {
+                    volume = evt.volume;
+                }
+                if evt.pitch == PROPERTY_UNUSED {
+                    continue;
+                }
+                let note = if is_drum {
+                    options
+                        .drum_map
+                        .get(usize::from(ch.instrument))
+                        .copied()
+                        .unwrap_or(FALLBACK_DRUM_NOTE)
+                } else {
+                    evt.pitch.saturating_add(PITCH_OFFSET).min(127)
+                };
+                let velocity = to_midi_range(volume, MAX_VOLUME).max(1);
+                // Notes are cut off by the next note on the same channel
+                let next = pitched
+                    .get(pitched.partition_point(|&pos| pos <= evt.position))
+                    .copied()
+                    .unwrap_or(self.repeat_end);
+                let end = evt
+                    .position
+                    .saturating_add(evt.length.max(1).into())
+                    .min(next);
+                events.push(TimedEvent {
+                    tick: evt.position,
+                    order: 2,
+                    bytes: vec![0x90 | midi_ch, note, velocity],
+                });
+                events.push(TimedEvent {
+                    tick: end,
+                    order: 0,
+                    bytes: vec![0x80 | midi_ch, note, 0],
+                });
+            }
+            write_track(&mut out, events);
+        }
+        out
+    }
+}
+
+impl Song {
+    /// Convert a Standard MIDI File to a song
+    ///
+    /// Notes are quantized to a grid of [`MidiImportOptions::steps_per_beat`] steps per beat,
+    /// and the tempo is taken from the first tempo change. Each track and MIDI channel pair
+    /// with melodic notes is assigned to a melody channel, in order. Percussion notes are
+    /// assigned to a drum channel per drum instrument. Notes that don't fit in the 8 melody
+    /// and 8 drum channels are dropped.
+    ///
+    /// Velocity is mapped to volume, and CC10 to pan. `loopStart`/`loopEnd` markers set the
+    /// repeat points. Without them, the song loops from the beginning to the end of the last
+    /// measure with notes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::MalformedMidi`] if the data is not a valid MIDI file, or it uses
+    /// SMPTE timing.
+    pub fn from_midi(data: &[u8], options: &MidiImportOptions) -> Result<Self, OrgError> {
+        let midi = MidiContents::read(data).ok_or(OrgError::MalformedMidi)?;
+        let steps_per_beat = options.steps_per_beat.max(1);
+        let quantize = |tick: u32| {
+            let steps = (u64::from(tick) * u64::from(steps_per_beat) * 2
+                + u64::from(midi.division))
+                / (u64::from(midi.division) * 2);
+            u32::try_from(steps).unwrap_or(u32::MAX)
+        };
+        let to_step = |note: &MidiNote, channel: u8, pitch: u8| {
+            let position = quantize(note.start);
+            Step {
+                position,
+                length: quantize(note.end).saturating_sub(position).clamp(1, 255),
+                pitch,
+                velocity: note.velocity,
+                pan: midi.pan_at(channel, note.start),
+            }
+        };
+
+        let mut song = Self::default();
+        let micros_per_beat = midi.micros_per_beat.unwrap_or(500_000);
+        let tempo_ms = (micros_per_beat + 500 * u32::from(steps_per_beat))
+            / (1000 * u32::from(steps_per_beat));
+        song.tempo_ms = u16::try_from(tempo_ms.max(1)).unwrap_or(u16::MAX);
+        song.steps_per_beat = steps_per_beat;
+        song.beats_per_measure = match midi.time_signature {
+            // Beats are quarter notes
+            Some((numerator, denominator @ 0..=2)) => {
+                numerator.saturating_mul(1 << (2 - denominator))
+            }
+            // Shorter beats are grouped in quarter notes, rounding up
+            Some((numerator, denominator)) => match 1u8.checked_shl(u32::from(denominator - 2)) {
+                Some(per_quarter) => numerator.div_ceil(per_quarter),
+                None => 1,
+            },
+            None => 4,
+        }
+        .max(1);
+
+        let mut lanes = Vec::new();
+        for (&(_, channel), notes) in &midi.voices {
+            let steps: Vec<_> = notes
+                .iter()
+                .map(|note| {
+                    // Notes outside of the Organya range are moved by octaves
+                    let mut key = note.key;
+                    while key < PITCH_OFFSET {
+                        key += 12;
+                    }
+                    let mut pitch = key - PITCH_OFFSET;
+                    while pitch > 95 {
+                        pitch -= 12;
+                    }
+                    to_step(note, channel, pitch)
+                })
+                .collect();
+            lanes.extend(split_lanes(&steps, options.polyphony));
+        }
+        for (ch, lane) in song.channels[..8].iter_mut().zip(lanes) {
+            ch.events = lane_events(&lane, false);
+        }
+
+        let mut drums: Vec<(u8, Vec<Step>)> = Vec::new();
+        for note in &midi.percussion {
+            let Some(&Some(instrument)) = options.drum_map.get(usize::from(note.key)) else {
+                continue;
+            };
+            let step = to_step(note, PERCUSSION_CHANNEL, options.drum_pitch);
+            match drums.iter_mut().find(|(ins, _)| *ins == instrument) {
+                Some((_, steps)) => steps.push(step),
+                None => drums.push((instrument, vec![step])),
+            }
+        }
+        for (ch, (instrument, steps)) in song.channels[8..].iter_mut().zip(drums) {
+            ch.instrument = instrument;
+            ch.events = lane_events(&steps, true);
+        }
+
+        let measure = u32::from(song.beats_per_measure) * u32::from(steps_per_beat);
+        let last_end = song
+            .channels
+            .iter()
+            .filter_map(|ch| ch.events.last())
+            .map(|evt| evt.position + u32::from(evt.length))
+            .max()
+            .unwrap_or(0);
+        song.repeat_start = midi.loop_start.map_or(0, quantize);
+        song.repeat_end = midi
+            .loop_end
+            .map_or_else(|| last_end.div_ceil(measure).max(1) * measure, quantize);
+        Ok(song)
+    }
+}
+
+/// Convert a monophonic lane of notes to Organya events
+fn lane_events(lane: &[Step], is_drum: bool) -> Vec<Event> {
+    let mut events: Vec<Event> = Vec::new();
+    for note in lane {
+        // Drum hits landing on the same step are merged
+        if is_drum && events.last().is_some_and(|e| e.position == note.position) {
+            continue;
+        }
+        let mut event = Event {
+            position: note.position,
+            pitch: note.pitch,
+            length: u8::try_from(note.length).unwrap_or(u8::MAX),
+            volume: note.velocity.min(127) * 2,
+            pan: PROPERTY_UNUSED,
+        };
+        // Written on every note, as consecutive notes alternate between sounds with their own pan
+        if let Some(value) = note.pan {
+            let scaled = (u16::from(value.min(127)) * u16::from(MAX_PAN) + 63) / 127;
+            event.pan = u8::try_from(scaled).unwrap();
+        }
+        events.push(event);
+    }
+    events
+}
+
+fn meta(tick: u32, kind: u8, data: &[u8]) -> TimedEvent {
+    let mut bytes = vec![0xFF, kind];
+    write_var_len(&mut bytes, u32::try_from(data.len()).unwrap());
+    bytes.extend_from_slice(data);
+    TimedEvent {
+        tick,
+        order: 1,
+        bytes,
+    }
+}
+
+fn write_track(out: &mut Vec<u8>, mut events: Vec<TimedEvent>) {
+    events.sort_by_key(|e| (e.tick, e.order));
+    let mut data = Vec::new();
+    let mut last_tick = 0;
+    for evt in events {
+        write_var_len(&mut data, evt.tick - last_tick);
+        data.extend_from_slice(&evt.bytes);
+        last_tick = evt.tick;
+    }
+    // End of track
+    data.extend_from_slice(&[0, 0xFF, 0x2F, 0]);
+    out.extend_from_slice(b"MTrk");
+    out.extend_from_slice(&u32::try_from(data.len()).unwrap().to_be_bytes());
+    out.extend_from_slice(&data);
+}
+
+/// Scale `value` from `0..=max` to the `0..=127` range of MIDI data bytes
+fn to_midi_range(value: u8, max: u8) -> u8 {
+    // The result is at most 127
+    #[expect(clippy::cast_possible_truncation)]
+    {
+        (u16::from(value.min(max)) * 127 / u16::from(max)) as u8
+    }
+}
+
+/// Read a MIDI variable length quantity
+fn read_var_len(read: &mut ReadCursor) -> Option<u32> {
+    let mut value = 0u32;
+    for _ in 0..4 {
+        let byte = read.next_u8()?;
+        value = (value << 7) | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Write a MIDI variable length quantity
+fn write_var_len(out: &mut Vec<u8>, value: u32) {
+    let mut shift = 28;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        out.push(0x80 | ((value >> shift) & 0x7F) as u8);
+        shift -= 7;
+    }
+    out.push((value & 0x7F) as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_tempo_saturates() {
+        let song = Song {
+            tempo_ms: 20_000,
+            steps_per_beat: 255,
+            ..Song::default()
+        };
+        let midi = song.to_midi(&MidiExportOptions::default());
+        let tempo = [0xFF, 0x51, 3, 0xFF, 0xFF, 0xFF];
+        assert!(midi.windows(tempo.len()).any(|w| w == tempo));
+    }
+
+    #[test]
+    fn pan_on_every_note() {
+        let mut song = Song::default();
+        song.channels[0].events = (0..4)
+            .map(|i| Event {
+                position: i * 2,
+                pitch: 40,
+                length: 1,
+                volume: 200,
+                pan: if i == 0 { 3 } else { PROPERTY_UNUSED },
+            })
+            .collect();
+        let midi = song.to_midi(&MidiExportOptions::default());
+        let imported = Song::from_midi(&midi, &MidiImportOptions::default()).unwrap();
+        let pans: Vec<_> = imported.channels[0].events.iter().map(|e| e.pan).collect();
+        assert_eq!(pans, [3; 4]);
+    }
+
+    #[test]
+    fn eighth_note_time_signatures() {
+        let midi = Song::default().to_midi(&MidiExportOptions::default());
+        let at = midi.windows(3).position(|w| w == [0xFF, 0x58, 4]).unwrap() + 3;
+        let beats = |numerator, denominator| {
+            let mut midi = midi.clone();
+            midi[at..at + 2].copy_from_slice(&[numerator, denominator]);
+            let song = Song::from_midi(&midi, &MidiImportOptions::default()).unwrap();
+            song.beats_per_measure
+        };
+        assert_eq!(beats(4, 2), 4);
+        assert_eq!(beats(2, 1), 4);
+        assert_eq!(beats(6, 3), 3);
+        assert_eq!(beats(3, 3), 2);
+        assert_eq!(beats(7, 4), 2);
+        assert_eq!(beats(1, 200), 1);
+    }
+
+    #[test]
+    fn drum_pan_is_left_out() {
+        let event = |position, pan| Event {
+            position,
+            pitch: 40,
+            length: 10,
+            volume: 200,
+            pan,
+        };
+        let mut song = Song {
+            repeat_end: u32::MAX,
+            ..Song::default()
+        };
+        song.channels[8].events = vec![event(0, 2), event(u32::MAX - 1, 10)];
+        let midi = song.to_midi(&MidiExportOptions::default());
+        assert!(
+            !midi
+                .windows(2)
+                .any(|w| w == [0xB0 | PERCUSSION_CHANNEL, 10])
+        );
+    }
+}
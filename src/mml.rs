@@ -0,0 +1,568 @@
+use crate::{
+    OrgError, PROPERTY_UNUSED, SyntaxError,
+    song::{Event, Song},
+};
+
+/// Octave notes start in, `o4` being the octave of middle C
+const DEFAULT_OCTAVE: u8 = 4;
+/// Default note length, as a fraction of a whole note
+const DEFAULT_LENGTH: u32 = 4;
+/// Default tempo, in beats per minute
+const DEFAULT_BPM: u32 = 120;
+/// Lowest and highest octaves that fit the Organya pitch range
+const OCTAVES: std::ops::RangeInclusive<u8> = 1..=8;
+/// Highest repeat count of a loop
+const MAX_LOOP_COUNT: u32 = 255;
+/// Maximum number of commands run once loops are expanded, to bound nested loops
+const MAX_EXPANDED_COMMANDS: usize = 1 << 22;
+
+impl Song {
+    /// Compile a song written in MML (Music Macro Language)
+    ///
+    /// Each line starts with the channels it applies to, `A` to `H` being the melody channels
+    /// and `I` to `P` the drum channels, followed by commands. A line with several channels,
+    /// like `AB`, applies its commands to each of them. Lines starting with `#` are directives,
+    /// and `;` starts a comment that runs to the end of the line.
+    ///
+    /// Commands:
+    /// - `c` `d` `e` `f` `g` `a` `b`: play a note, optionally followed by `+` or `#` (sharp) or
+    ///   `-` (flat), a length and dots. `c4.` is a dotted quarter note.
+    /// - `r`: rest, with an optional length and dots.
+    /// - `^`: extend the previous note, with an optional length and dots.
+    /// - `o4`: set the octave, from 1 to 8. `o4 c` is middle C.
+    /// - `>` and `<`: go up or down an octave.
+    /// - `l8`: set the length of notes and rests without one.
+    /// - `v200`: set the volume, from 0 to 254.
+    /// - `p6`: set the pan, from 0 (left) to 12 (right).
+    ///
+    ///   Once set, the volume and pan are written on every note, because consecutive notes
+    ///   alternate between sounds that each keep their own.
+    /// - `@3`: set the instrument of the channel. A channel has a single instrument.
+    /// - `[` ... `]3`: repeat the enclosed commands 3 times, or 2 times without a count. The
+    ///   count is at most 255. Loops can be nested, and must be closed on the same line.
+    /// - `L`: the song loops back to this point when it ends.
+    ///
+    /// Directives:
+    /// - `#tempo 120`: tempo in beats per minute.
+    /// - `#beats 4`: beats per measure.
+    /// - `#steps 4`: steps per beat, the shortest note. It must come before any notes.
+    /// - `#pizzicato AB`: the notes of these melody channels don't sustain.
+    ///
+    /// The song ends at the end of the measure where the longest channel ends.
+    ///
+    /// ```text
+    /// #tempo 140
+    /// A @1 o4 l8 v200 c d e f L g4 g4 [a g]2
+    /// I @0 l4 c c c c L [c c c c]2
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::Syntax`] with the location of the first error if the source is not
+    /// valid.
+    pub fn from_mml(source: &str) -> Result<Self, OrgError> {
+        let mut compiler = Compiler {
+            channels: std::array::from_fn(|_| ChannelState::default()),
+            song: Self {
+                beats_per_measure: 4,
+                steps_per_beat: 4,
+                ..Self::default()
+            },
+            bpm: DEFAULT_BPM,
+            repeat_start: None,
+            has_notes: false,
+            expanded: 0,
+        };
+        for (line, text) in (1..).zip(source.lines()) {
+            let chars: Vec<char> = text.chars().take_while(|&c| c != ';').collect();
+            let mut parser = Parser {
+                chars: &chars,
+                pos: 0,
+                line,
+            };
+            compiler.line(&mut parser)?;
+        }
+        Ok(compiler.finish())
+    }
+}
+
+/// Parses the characters of one line
+struct Parser<'a> {
+    chars: &'a [char],
+    pos: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error_at(&self, pos: usize, message: &str) -> OrgError {
+        OrgError::Syntax(SyntaxError {
+            line: self.line,
+            column: pos + 1,
+            message: message.to_owned(),
+        })
+    }
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn number(&mut self) -> Result<Option<u32>, OrgError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| self.error_at(start, "number too large"))
+    }
+    fn required_number(&mut self, max: u32) -> Result<u32, OrgError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.number()? {
+            Some(n) if n <= max => Ok(n),
+            Some(_) => Err(self.error_at(start, &format!("must be at most {max}"))),
+            None => Err(self.error_at(start, "expected a number")),
+        }
+    }
+    /// An optional length followed by dots
+    fn length(&mut self) -> Result<Length, OrgError> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        let value = self.number()?;
+        let mut dots = 0;
+        while self.eat('.') {
+            dots += 1;
+        }
+        Ok(Length { pos, value, dots })
+    }
+    /// Parse commands until the end of the line, or the end of a loop
+    fn commands(&mut self, loop_start: Option<usize>) -> Result<Vec<Command>, OrgError> {
+        let mut commands = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let pos = self.pos;
+            let Some(c) = self.peek() else {
+                return match loop_start {
+                    Some(start) => Err(self.error_at(start, "unclosed loop")),
+                    None => Ok(commands),
+                };
+            };
+            self.pos += 1;
+            let kind = match c {
+                'c' | 'd' | 'e' | 'f' | 'g' | 'a' | 'b' => {
+                    let semitone = match c {
+                        'c' => 0,
+                        'd' => 2,
+                        'e' => 4,
+                        'f' => 5,
+                        'g' => 7,
+                        'a' => 9,
+                        _ => 11,
+                    };
+                    let accidental = if self.eat('+') || self.eat('#') {
+                        1
+                    } else if self.eat('-') {
+                        -1
+                    } else {
+                        0
+                    };
+                    CommandKind::Note(semitone + accidental, self.length()?)
+                }
+                'r' => CommandKind::Rest(self.length()?),
+                '^' => CommandKind::Tie(self.length()?),
+                'o' => CommandKind::Octave(self.required_number(u32::from(*OCTAVES.end()))?),
+                '>' => CommandKind::OctaveUp,
+                '<' => CommandKind::OctaveDown,
+                'l' => CommandKind::DefaultLength(self.length()?),
+                'v' => CommandKind::Volume(self.required_number(254)?),
+                'p' => CommandKind::Pan(self.required_number(12)?),
+                '@' => CommandKind::Instrument(self.required_number(255)?),
+                'L' => CommandKind::RepeatStart,
+                '[' => {
+                    let body = self.commands(Some(pos))?;
+                    self.skip_whitespace();
+                    let count_pos = self.pos;
+                    let count = match self.number()? {
+                        Some(count) if count > MAX_LOOP_COUNT => {
+                            return Err(self.error_at(
+                                count_pos,
+                                &format!("loop count must be at most {MAX_LOOP_COUNT}"),
+                            ));
+                        }
+                        count => count.unwrap_or(2),
+                    };
+                    CommandKind::Loop(body, count)
+                }
+                ']' if loop_start.is_some() => return Ok(commands),
+                ']' => return Err(self.error_at(pos, "`]` without a matching `[`")),
+                _ => return Err(self.error_at(pos, &format!("unknown command `{c}`"))),
+            };
+            commands.push(Command { pos, kind });
+        }
+    }
+}
+
+/// A note length, as written
+struct Length {
+    pos: usize,
+    /// Fraction of a whole note, or `None` for the default length
+    value: Option<u32>,
+    dots: u8,
+}
+
+struct Command {
+    /// Index of the command in the line
+    pos: usize,
+    kind: CommandKind,
+}
+
+enum CommandKind {
+    /// Semitone from C, which can be -1 or 12 with accidentals
+    Note(i8, Length),
+    Rest(Length),
+    Tie(Length),
+    Octave(u32),
+    OctaveUp,
+    OctaveDown,
+    DefaultLength(Length),
+    Volume(u32),
+    Pan(u32),
+    Instrument(u32),
+    RepeatStart,
+    Loop(Vec<Command>, u32),
+}
+
+/// The state of a channel while compiling
+struct ChannelState {
+    position: u32,
+    octave: u8,
+    /// Default length in steps
+    length: Option<u32>,
+    volume: u8,
+    pan: u8,
+    instrument: Option<u8>,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            position: 0,
+            octave: DEFAULT_OCTAVE,
+            length: None,
+            volume: PROPERTY_UNUSED,
+            pan: PROPERTY_UNUSED,
+            instrument: None,
+        }
+    }
+}
+
+struct Compiler {
+    channels: [ChannelState; 16],
+    song: Song,
+    bpm: u32,
+    repeat_start: Option<u32>,
+    has_notes: bool,
+    /// Number of commands run so far
+    expanded: usize,
+}
+
+impl Compiler {
+    fn line(&mut self, parser: &mut Parser) -> Result<(), OrgError> {
+        parser.skip_whitespace();
+        if parser.eat('#') {
+            return self.directive(parser);
+        }
+        let mut selected = Vec::new();
+        while let Some(c @ 'A'..='P') = parser.peek() {
+            selected.push(usize::from(c as u8 - b'A'));
+            parser.pos += 1;
+        }
+        if selected.is_empty() {
+            return match parser.peek() {
+                Some(_) => Err(parser.error_at(parser.pos, "expected channels from `A` to `P`")),
+                None => Ok(()),
+            };
+        }
+        if parser.peek().is_some_and(|c| !c.is_whitespace()) {
+            return Err(parser.error_at(parser.pos, "expected a space after the channels"));
+        }
+        let commands = parser.commands(None)?;
+        for ch in selected {
+            self.run(parser, ch, &commands)?;
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, parser: &mut Parser) -> Result<(), OrgError> {
+        let start = parser.pos;
+        while parser.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            parser.pos += 1;
+        }
+        let name: String = parser.chars[start..parser.pos].iter().collect();
+        match name.as_str() {
+            "tempo" => {
+                self.bpm = parser.required_number(u32::from(u16::MAX))?.max(1);
+            }
+            "beats" => {
+                self.song.beats_per_measure = to_u8(parser.required_number(255)?.max(1));
+            }
+            "steps" => {
+                parser.skip_whitespace();
+                let pos = parser.pos;
+                let steps = parser.required_number(255)?.max(1);
+                if self.has_notes {
+                    return Err(parser.error_at(pos, "`#steps` must come before any notes"));
+                }
+                self.song.steps_per_beat = to_u8(steps);
+            }
+            "pizzicato" => {
+                parser.skip_whitespace();
+                while let Some(c @ 'A'..='H') = parser.peek() {
+                    self.song.channels[usize::from(c as u8 - b'A')].pizzicato = true;
+                    parser.pos += 1;
+                }
+            }
+            _ => return Err(parser.error_at(start, &format!("unknown directive `#{name}`"))),
+        }
+        parser.skip_whitespace();
+        match parser.peek() {
+            Some(_) => Err(parser.error_at(parser.pos, "unexpected text after the directive")),
+            None => Ok(()),
+        }
+    }
+
+    /// Convert a length to steps
+    fn steps(
+        &self,
+        parser: &Parser,
+        length: &Length,
+        default: Option<u32>,
+    ) -> Result<u32, OrgError> {
+        let whole = u32::from(self.song.steps_per_beat) * 4;
+        let mut steps = match length.value {
+            Some(0) => return Err(parser.error_at(length.pos, "length must be at least 1")),
+            Some(value) if whole % value == 0 => whole / value,
+            Some(value) => {
+                return Err(parser.error_at(
+                    length.pos,
+                    &format!("a 1/{value} note doesn't fit in steps of 1/{whole}"),
+                ));
+            }
+            None => default.unwrap_or(whole / DEFAULT_LENGTH).max(1),
+        };
+        let mut dot = steps;
+        for _ in 0..length.dots {
+            if dot % 2 != 0 {
+                return Err(parser.error_at(length.pos, "dotted length doesn't fit in steps"));
+            }
+            dot /= 2;
+            steps += dot;
+        }
+        Ok(steps)
+    }
+
+    fn run(&mut self, parser: &Parser, ch: usize, commands: &[Command]) -> Result<(), OrgError> {
+        for command in commands {
+            let error = |message: &str| parser.error_at(command.pos, message);
+            self.expanded += 1;
+            if self.expanded > MAX_EXPANDED_COMMANDS {
+                return Err(error("too many commands once loops are expanded"));
+            }
+            let default_length = self.channels[ch].length;
+            match &command.kind {
+                CommandKind::Note(semitone, length) => {
+                    let steps = self.steps(parser, length, default_length)?;
+                    self.note(parser, command.pos, ch, *semitone, steps)?;
+                }
+                CommandKind::Rest(length) => {
+                    let steps = self.steps(parser, length, default_length)?;
+                    self.advance(parser, command.pos, ch, steps)?;
+                }
+                CommandKind::Tie(length) => {
+                    let steps = self.steps(parser, length, default_length)?;
+                    let position = self.channels[ch].position;
+                    let Some(last) = self.song.channels[ch]
+                        .events
+                        .last_mut()
+                        .filter(|e| e.position + u32::from(e.length) == position)
+                    else {
+                        return Err(error("`^` must follow a note"));
+                    };
+                    last.length = u32::from(last.length)
+                        .checked_add(steps)
+                        .and_then(|l| u8::try_from(l).ok())
+                        .ok_or_else(|| error("notes can't be longer than 255 steps"))?;
+                    self.advance(parser, command.pos, ch, steps)?;
+                }
+                CommandKind::Octave(octave) => {
+                    let octave = to_u8(*octave);
+                    if !OCTAVES.contains(&octave) {
+                        return Err(error("octave must be from 1 to 8"));
+                    }
+                    self.channels[ch].octave = octave;
+                }
+                CommandKind::OctaveUp | CommandKind::OctaveDown => {
+                    let state = &mut self.channels[ch];
+                    let octave = if matches!(command.kind, CommandKind::OctaveUp) {
+                        state.octave + 1
+                    } else {
+                        state.octave - 1
+                    };
+                    if !OCTAVES.contains(&octave) {
+                        return Err(error("octave must be from 1 to 8"));
+                    }
+                    state.octave = octave;
+                }
+                CommandKind::DefaultLength(length) => {
+                    let steps = self.steps(parser, length, None)?;
+                    self.channels[ch].length = Some(steps);
+                }
+                CommandKind::Volume(volume) => self.channels[ch].volume = to_u8(*volume),
+                CommandKind::Pan(pan) => self.channels[ch].pan = to_u8(*pan),
+                CommandKind::Instrument(instrument) => {
+                    let instrument = to_u8(*instrument);
+                    let state = &mut self.channels[ch];
+                    if state.instrument.is_some_and(|i| i != instrument) {
+                        return Err(error("a channel can only use one instrument"));
+                    }
+                    if ch < 8 && usize::from(instrument) >= crate::MELODY_WAVE_COUNT {
+                        return Err(error("melody instruments must be below 100"));
+                    }
+                    state.instrument = Some(instrument);
+                    self.song.channels[ch].instrument = instrument;
+                }
+                CommandKind::RepeatStart => {
+                    let position = self.channels[ch].position;
+                    if self.repeat_start.is_some_and(|p| p != position) {
+                        return Err(error(
+                            "`L` is at a different position than in other channels",
+                        ));
+                    }
+                    self.repeat_start = Some(position);
+                }
+                CommandKind::Loop(body, count) => {
+                    for _ in 0..*count {
+                        self.run(parser, ch, body)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn note(
+        &mut self,
+        parser: &Parser,
+        pos: usize,
+        ch: usize,
+        semitone: i8,
+        steps: u32,
+    ) -> Result<(), OrgError> {
+        let state = &mut self.channels[ch];
+        let pitch = (i32::from(state.octave) - 1) * 12 + i32::from(semitone);
+        let pitch = u8::try_from(pitch)
+            .ok()
+            .filter(|&p| p < 96)
+            .ok_or_else(|| parser.error_at(pos, "note out of range"))?;
+        let length = u8::try_from(steps)
+            .map_err(|_| parser.error_at(pos, "notes can't be longer than 255 steps"))?;
+        let event = Event {
+            position: state.position,
+            pitch,
+            length,
+            volume: state.volume,
+            pan: state.pan,
+        };
+        self.song.channels[ch].events.push(event);
+        self.has_notes = true;
+        self.advance(parser, pos, ch, steps)
+    }
+
+    fn advance(
+        &mut self,
+        parser: &Parser,
+        pos: usize,
+        ch: usize,
+        steps: u32,
+    ) -> Result<(), OrgError> {
+        let state = &mut self.channels[ch];
+        state.position = state
+            .position
+            .checked_add(steps)
+            .ok_or_else(|| parser.error_at(pos, "song too long"))?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Song {
+        let song = &mut self.song;
+        let steps_per_minute = self.bpm * u32::from(song.steps_per_beat);
+        let tempo_ms = (60_000 + steps_per_minute / 2) / steps_per_minute;
+        song.tempo_ms = u16::try_from(tempo_ms.max(1)).unwrap_or(u16::MAX);
+        let measure = u32::from(song.beats_per_measure) * u32::from(song.steps_per_beat);
+        let end = self
+            .channels
+            .iter()
+            .map(|ch| ch.position)
+            .max()
+            .unwrap_or(0);
+        song.repeat_end = end.div_ceil(measure).max(1).saturating_mul(measure);
+        song.repeat_start = self.repeat_start.unwrap_or(0);
+        self.song
+    }
+}
+
+/// Narrow a number that was checked to fit in a byte
+fn to_u8(value: u32) -> u8 {
+    u8::try_from(value).unwrap_or(u8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(source: &str) -> String {
+        match Song::from_mml(source) {
+            Err(OrgError::Syntax(error)) => error.message,
+            other => panic!("expected a syntax error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn volume_and_pan_on_every_note() {
+        let song = Song::from_mml("A v100 p3 c c L c\nA r c").unwrap();
+        let events = &song.channels[0].events;
+        assert_eq!(events.len(), 4);
+        assert!(events.iter().all(|e| e.volume == 100 && e.pan == 3));
+        let song = Song::from_mml("A c v50 c").unwrap();
+        let volumes: Vec<_> = song.channels[0].events.iter().map(|e| e.volume).collect();
+        assert_eq!(volumes, [PROPERTY_UNUSED, 50]);
+    }
+
+    #[test]
+    fn loop_limits() {
+        let song = Song::from_mml("#steps 1\nA l1 [c]255").unwrap();
+        assert_eq!(song.channels[0].events.len(), 255);
+        assert_eq!(message("A [c]256"), "loop count must be at most 255");
+        assert_eq!(message("A [c]4000000000"), "loop count must be at most 255");
+        assert_eq!(
+            message("A [[[[r]255]255]255]255"),
+            "too many commands once loops are expanded"
+        );
+    }
+}
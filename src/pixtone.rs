@@ -0,0 +1,294 @@
+use {
+    crate::{OrgError, soundbank::Samples},
+    std::path::Path,
+};
+
+/// Sample rate PixTone sounds are rendered at
+pub const PIXTONE_SAMPLE_RATE: u16 = 22050;
+/// Maximum number of channels of a PixTone sound
+pub const PIXTONE_CHANNEL_COUNT: usize = 4;
+/// Number of samples in a waveform or envelope table
+const TABLE_LEN: usize = 256;
+
+/// Waveform of a PixTone oscillator
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum PixToneWaveform {
+    /// Sine wave
+    #[default]
+    Sine,
+    /// Triangle wave
+    Triangle,
+    /// Rising sawtooth wave
+    SawUp,
+    /// Falling sawtooth wave
+    SawDown,
+    /// Square wave
+    Square,
+    /// White noise
+    Noise,
+}
+
+impl PixToneWaveform {
+    /// The waveform for its number in PixTone parameters
+    #[must_use]
+    pub const fn from_u8(model: u8) -> Option<Self> {
+        Some(match model {
+            0 => Self::Sine,
+            1 => Self::Triangle,
+            2 => Self::SawUp,
+            3 => Self::SawDown,
+            4 => Self::Square,
+            5 => Self::Noise,
+            _ => return None,
+        })
+    }
+    /// The number of the waveform in PixTone parameters
+    #[must_use]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+/// An oscillator of a PixTone channel
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PixToneOscillator {
+    /// Shape of the oscillator
+    pub waveform: PixToneWaveform,
+    /// Number of periods over the length of the sound
+    pub frequency: f64,
+    /// Amplitude, where 64 is full scale
+    pub amplitude: i32,
+    /// Starting phase, where 256 is a full period
+    pub offset: i32,
+}
+
+/// A channel of a PixTone sound
+///
+/// The main oscillator makes the sound. The pitch oscillator modulates its frequency, and the
+/// volume oscillator its amplitude. The envelope goes from the initial value through three
+/// points, then to 0 at the end of the sound.
+#[derive(Clone, Default, Debug)]
+pub struct PixToneChannel {
+    /// Whether the channel is part of the sound
+    pub enabled: bool,
+    /// Length of the channel, in samples
+    pub size: u32,
+    /// The oscillator that makes the sound
+    pub main: PixToneOscillator,
+    /// The oscillator that modulates the frequency of the main oscillator
+    pub pitch: PixToneOscillator,
+    /// The oscillator that modulates the amplitude of the main oscillator
+    pub volume: PixToneOscillator,
+    /// Initial value of the envelope, where 64 is full scale
+    pub envelope_initial: i32,
+    /// `(x, y)` points of the envelope, where an `x` of 256 is the end of the sound
+    pub envelope: [(i32, i32); 3],
+}
+
+/// A PixTone sound effect, synthesized from parameters like the sound effects of Cave Story
+///
+/// Rendered sounds can be played along with music by [`Player::play_effect`](crate::Player::play_effect).
+#[derive(Clone, Default, Debug)]
+pub struct PixTone {
+    /// The channels mixed together to make the sound, at most [`PIXTONE_CHANNEL_COUNT`]
+    pub channels: Vec<PixToneChannel>,
+}
+
+impl PixTone {
+    /// Parse PixTone parameters in the text format of the PixTone editor (`.pxt`)
+    ///
+    /// Each line is a `key:value` pair, and each channel starts with a `use` key. Unknown keys
+    /// are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::MalformedPixTone`] if a line isn't a `key:value` pair, a value
+    /// isn't a number, a waveform number is out of range, or there are too many channels.
+    pub fn parse(text: &str) -> Result<Self, OrgError> {
+        Self::parse_inner(text).ok_or(OrgError::MalformedPixTone)
+    }
+    /// Load PixTone parameters from a file. See [`Self::parse`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`std::io::Error`] if reading the file failed.
+    /// - Returns [`OrgError::MalformedPixTone`] if the parameters are invalid.
+    pub fn load_file(file_path: &Path) -> Result<Self, OrgError> {
+        let text = std::fs::read_to_string(file_path)?;
+        Self::parse(&text)
+    }
+    fn parse_inner(text: &str) -> Option<Self> {
+        let mut channels: Vec<PixToneChannel> = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(':')?;
+            let (key, value) = (key.trim(), value.trim());
+            if key == "use" {
+                if channels.len() == PIXTONE_CHANNEL_COUNT {
+                    return None;
+                }
+                channels.push(PixToneChannel::default());
+            }
+            let ch = channels.last_mut()?;
+            let number: f64 = value.parse().ok()?;
+            // Integer parameters are truncated
+            #[expect(clippy::cast_possible_truncation)]
+            let int = number as i32;
+            let waveform = || PixToneWaveform::from_u8(u8::try_from(int).ok()?);
+            match key {
+                "use" => ch.enabled = int != 0,
+                "size" => ch.size = u32::try_from(int).ok()?,
+                "main_model" => ch.main.waveform = waveform()?,
+                "main_freq" => ch.main.frequency = number,
+                "main_top" => ch.main.amplitude = int,
+                "main_offset" => ch.main.offset = int,
+                "pitch_model" => ch.pitch.waveform = waveform()?,
+                "pitch_freq" => ch.pitch.frequency = number,
+                "pitch_top" => ch.pitch.amplitude = int,
+                "pitch_offset" => ch.pitch.offset = int,
+                "volume_model" => ch.volume.waveform = waveform()?,
+                "volume_freq" => ch.volume.frequency = number,
+                "volume_top" => ch.volume.amplitude = int,
+                "volume_offset" => ch.volume.offset = int,
+                "initialY" => ch.envelope_initial = int,
+                "ax" => ch.envelope[0].0 = int,
+                "ay" => ch.envelope[0].1 = int,
+                "bx" => ch.envelope[1].0 = int,
+                "by" => ch.envelope[1].1 = int,
+                "cx" => ch.envelope[2].0 = int,
+                "cy" => ch.envelope[2].1 = int,
+                _ => {}
+            }
+        }
+        Some(Self { channels })
+    }
+
+    /// Render the sound as 8 bit samples at [`PIXTONE_SAMPLE_RATE`]
+    ///
+    /// The enabled channels are mixed together, clipping like PixTone does.
+    #[must_use]
+    pub fn render(&self) -> Samples {
+        let tables = wave_tables();
+        let channels: Vec<_> = self.channels.iter().filter(|ch| ch.enabled).collect();
+        let len = channels.iter().map(|ch| ch.size).max().unwrap_or(0) as usize;
+        let mut mixed = vec![0i8; len];
+        for ch in channels {
+            for (mixed, sample) in mixed.iter_mut().zip(ch.render(&tables)) {
+                *mixed = mixed.saturating_add(sample);
+            }
+        }
+        Samples::I8(mixed)
+    }
+}
+
+impl PixToneChannel {
+    /// Render the channel on its own, as 8 bit samples at [`PIXTONE_SAMPLE_RATE`]
+    fn render(&self, tables: &[[i8; TABLE_LEN]; 6]) -> Vec<i8> {
+        let envelope = self.envelope_table();
+        let main = &tables[usize::from(self.main.waveform.to_u8())];
+        let pitch = &tables[usize::from(self.pitch.waveform.to_u8())];
+        let volume = &tables[usize::from(self.volume.waveform.to_u8())];
+        let size = f64::from(self.size);
+        let step = |osc: &PixToneOscillator| {
+            if osc.frequency == 0.0 {
+                0.0
+            } else {
+                256.0 / (size / osc.frequency)
+            }
+        };
+        let (main_step, pitch_step, volume_step) =
+            (step(&self.main), step(&self.pitch), step(&self.volume));
+        let mut main_phase = f64::from(self.main.offset);
+        let mut pitch_phase = f64::from(self.pitch.offset);
+        let mut volume_phase = f64::from(self.volume.offset);
+        let phase_index = |phase: f64| {
+            // Phases are truncated like PixTone does, and wrapped to the table
+            #[expect(clippy::cast_possible_truncation)]
+            {
+                (phase as i32).rem_euclid(256) as usize
+            }
+        };
+
+        let mut out = Vec::with_capacity(self.size as usize);
+        for i in 0..self.size {
+            let main_value = i32::from(main[phase_index(main_phase)]);
+            let pitch_value = i32::from(pitch[phase_index(pitch_phase)]);
+            let volume_value = i32::from(volume[phase_index(volume_phase)]);
+            // Truncation is intended, to match PixTone
+            #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let point = (f64::from(i) * 256.0 / size) as usize;
+            let modulation = volume_value.wrapping_mul(self.volume.amplitude) / 64 + 64;
+            let sample =
+                (main_value.wrapping_mul(self.main.amplitude) / 64).wrapping_mul(modulation) / 64;
+            let sample = sample.wrapping_mul(i32::from(envelope[point.min(TABLE_LEN - 1)])) / 64;
+            // PixTone stores the sample as an unsigned byte, so it wraps around
+            out.push(sample.to_le_bytes()[0].cast_signed());
+
+            let depth = f64::from(self.pitch.amplitude);
+            // The operations are in the same order as PixTone, to round the same way
+            if pitch_value < 0 {
+                main_phase +=
+                    main_step - main_step * 0.5 * f64::from(-pitch_value) * depth / 64.0 / 64.0;
+            } else {
+                main_phase +=
+                    main_step + main_step * 2.0 * f64::from(pitch_value) * depth / 64.0 / 64.0;
+            }
+            pitch_phase += pitch_step;
+            volume_phase += volume_step;
+        }
+        out
+    }
+
+    /// The envelope over the length of the sound, in 256 points
+    fn envelope_table(&self) -> [i8; TABLE_LEN] {
+        let mut table = [0i8; TABLE_LEN];
+        let mut i = 0;
+        let (mut x, mut y) = (0, self.envelope_initial);
+        for (next_x, next_y) in self.envelope.into_iter().chain([(256, 0)]) {
+            let slope = f64::from(next_y - y) / f64::from(next_x - x);
+            let mut value = f64::from(y);
+            while i < usize::try_from(next_x).unwrap_or(0).min(TABLE_LEN) {
+                // PixTone stores the value as a signed byte, so it wraps around
+                #[expect(clippy::cast_possible_truncation)]
+                {
+                    table[i] = (value as i32).to_le_bytes()[0].cast_signed();
+                }
+                value += slope;
+                i += 1;
+            }
+            (x, y) = (next_x, next_y);
+        }
+        table
+    }
+}
+
+/// Build the PixTone waveform tables, from -64 to 64
+fn wave_tables() -> [[i8; TABLE_LEN]; 6] {
+    let mut tables = [[0i8; TABLE_LEN]; 6];
+    let [sine, triangle, saw_up, saw_down, square, noise] = &mut tables;
+    for (i, value) in (0i32..).zip(sine) {
+        // Truncation is intended, to match PixTone
+        #[expect(clippy::cast_possible_truncation)]
+        {
+            *value = ((f64::from(i) * 6.283_184 / 256.0).sin() * 64.0) as i8;
+        }
+    }
+    let triangle_values = (0..64).chain((-63..=64).rev()).chain(-64..0);
+    for (value, sample) in triangle.iter_mut().zip(triangle_values) {
+        *value = sample;
+    }
+    for (i, ((up, down), square)) in (0i8..=i8::MAX)
+        .flat_map(|i| [i, i])
+        .zip(saw_up.iter_mut().zip(saw_down.iter_mut()).zip(square))
+    {
+        *up = i - 64;
+        *down = 64 - i;
+        *square = if i < 64 { 64 } else { -64 };
+    }
+    // PixTone uses the random number generator of the MSVC runtime, seeded with 0
+    let mut seed = 0u32;
+    for value in noise {
+        seed = seed.wrapping_mul(214_013).wrapping_add(2_531_011);
+        *value = (seed >> 16).to_le_bytes()[0].cast_signed() / 2;
+    }
+    tables
+}
@@ -0,0 +1,289 @@
+use {
+    crate::{
+        OrgError, PROPERTY_UNUSED,
+        read_cursor::ReadCursor,
+        song::{Event, Song},
+        soundbank::{MELODY_WAVE_LEN, Samples, Soundbank},
+    },
+    std::path::Path,
+};
+
+/// Number of melody tracks in a PiyoPiyo song
+pub const PIYO_TRACK_COUNT: usize = 3;
+/// Number of points of a PiyoPiyo volume envelope
+pub const PIYO_ENVELOPE_LEN: usize = 64;
+/// Number of keys a melody track spans, starting from its octave
+const KEY_COUNT: u8 = 24;
+/// Records hold the keys in their low bits, and the pan in their high byte
+const KEY_MASK: u32 = 0x00FF_FFFF;
+/// Highest value of an envelope point
+const MAX_ENVELOPE: u8 = 128;
+/// Highest value of a track volume
+const MAX_VOLUME: u32 = 300;
+/// PiyoPiyo renders notes at this sample rate, which note lengths are expressed in
+const NOTE_RATE: u32 = 22050;
+/// Drum pitch that plays drum samples at their recorded rate
+const DRUM_PITCH: u8 = 27;
+/// Attenuation of the quiet variant of drums, in hundredths of a decibel
+const QUIET_DRUM_DB: i32 = -600;
+
+/// A melody track of a PiyoPiyo song
+#[derive(Clone)]
+pub struct PiyoTrack {
+    /// The octave of the lowest key of the track
+    pub octave: u8,
+    /// Length of notes, in samples at 22050 Hz
+    pub length: u32,
+    /// Volume of the track, from 0 to 300
+    pub volume: u32,
+    /// The waveform of the track
+    pub wave: [i8; MELODY_WAVE_LEN],
+    /// Volume of notes over their length, from 0 to 128
+    pub envelope: [u8; PIYO_ENVELOPE_LEN],
+    /// One record per step. The low 24 bits are the keys that start playing, and the high
+    /// byte is the pan, from 1 (left) to 7 (right), or 0 to keep the current one.
+    pub records: Vec<u32>,
+}
+
+/// A PiyoPiyo song (`.pmd`), the format of the PiyoPiyo tracker used by Ikachan
+///
+/// PiyoPiyo songs have 3 polyphonic melody tracks with their own waveforms and envelopes, and
+/// a drum track. They can be played by converting them with [`Self::to_song`].
+#[derive(Clone)]
+pub struct PiyoSong {
+    /// Milliseconds per step
+    pub tempo_ms: u32,
+    /// The step at which the song starts repeating
+    pub repeat_start: u32,
+    /// The step at which the song ends
+    pub repeat_end: u32,
+    /// The melody tracks
+    pub tracks: [PiyoTrack; PIYO_TRACK_COUNT],
+    /// Volume of the drum track, from 0 to 300
+    pub drum_volume: u32,
+    /// One record per step, laid out like melody records. Each pair of bits is a drum, with
+    /// the odd bit playing it quieter.
+    pub drum_records: Vec<u32>,
+}
+
+impl PiyoSong {
+    /// Read a PiyoPiyo song
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::MalformedPiyo`] if the data is not a valid PiyoPiyo song.
+    pub fn read(data: &[u8]) -> Result<Self, OrgError> {
+        Self::read_inner(data).ok_or(OrgError::MalformedPiyo)
+    }
+    /// Load a PiyoPiyo song from a file. See [`Self::read`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`std::io::Error`] if reading the file failed.
+    /// - Returns [`OrgError::MalformedPiyo`] if the data is not a valid PiyoPiyo song.
+    pub fn load_file(file_path: &Path) -> Result<Self, OrgError> {
+        let buffer = std::fs::read(file_path)?;
+        Self::read(&buffer)
+    }
+    fn read_inner(data: &[u8]) -> Option<Self> {
+        let mut read = ReadCursor(data);
+        if read.next_bytes()? != b"PMD" {
+            return None;
+        }
+        // Whether the song can be edited
+        read.next_u8()?;
+        let records_offset = usize::try_from(read.next_u32_le()?).ok()?;
+        let tempo_ms = read.next_u32_le()?;
+        let repeat_start = read.next_u32_le()?;
+        let repeat_end = read.next_u32_le()?;
+        let step_count = usize::try_from(read.next_u32_le()?).ok()?;
+        let mut tracks = Vec::with_capacity(PIYO_TRACK_COUNT);
+        for _ in 0..PIYO_TRACK_COUNT {
+            let octave = read.next_u8()?;
+            // Icon, and 2 unknown bytes
+            read.next_bytes::<3>()?;
+            let length = read.next_u32_le()?;
+            let volume = read.next_u32_le()?;
+            read.next_bytes::<8>()?;
+            let wave = read.next_bytes::<MELODY_WAVE_LEN>()?.map(u8::cast_signed);
+            let envelope = *read.next_bytes::<PIYO_ENVELOPE_LEN>()?;
+            tracks.push(PiyoTrack {
+                octave,
+                length,
+                volume,
+                wave,
+                envelope,
+                records: Vec::new(),
+            });
+        }
+        let drum_volume = read.next_u32_le()?;
+
+        let mut read = ReadCursor(data.get(records_offset..)?);
+        let mut read_records = || {
+            let bytes = read.next_n_bytes(step_count.checked_mul(4)?)?;
+            let (records, []) = bytes.as_chunks() else {
+                unreachable!()
+            };
+            Some(records.iter().map(|&r| u32::from_le_bytes(r)).collect())
+        };
+        for track in &mut tracks {
+            track.records = read_records()?;
+        }
+        let drum_records = read_records()?;
+        Some(Self {
+            tempo_ms,
+            repeat_start,
+            repeat_end,
+            tracks: tracks.try_into().ok()?,
+            drum_volume,
+            drum_records,
+        })
+    }
+
+    /// Convert the song to an Organya song, and a soundbank with the waveforms of its tracks
+    ///
+    /// The waveforms of the tracks are the melody waves 0 to 2 of the returned soundbank, and
+    /// the drums are taken from `drums`. Drums are mapped to the drums of the same index,
+    /// starting from 0.
+    ///
+    /// The conversion is approximate:
+    /// - Chords are spread across melody channels. Each track gets as many channels as its
+    ///   largest chord, as long as there are channels left, and notes that don't fit are
+    ///   dropped. Likewise, each drum gets its own channel while there are channels left.
+    /// - Envelopes are approximated with a volume change on each step of a note.
+    #[must_use]
+    pub fn to_song(&self, drums: &Soundbank) -> (Song, Soundbank) {
+        let mut song = Song {
+            tempo_ms: u16::try_from(self.tempo_ms.max(1)).unwrap_or(u16::MAX),
+            repeat_start: self.repeat_start.min(self.repeat_end),
+            repeat_end: self.repeat_end,
+            beats_per_measure: 4,
+            steps_per_beat: 4,
+            ..Song::default()
+        };
+        let mut soundbank = drums.clone();
+
+        let mut free_channels = 0..8;
+        for (instrument, track) in (0u8..).zip(&self.tracks) {
+            soundbank.melody_waves[usize::from(instrument)] = Samples::I8(track.wave.to_vec());
+            let polyphony = track
+                .records
+                .iter()
+                .map(|r| (r & KEY_MASK).count_ones())
+                .max()
+                .unwrap_or(0);
+            let channels: Vec<usize> = free_channels.by_ref().take(polyphony as usize).collect();
+            for &ch in &channels {
+                song.channels[ch].instrument = instrument;
+            }
+            self.convert_track(track, &channels, &mut song);
+        }
+
+        let mut drum_channels: Vec<(u8, usize)> = Vec::new();
+        let mut free_channels = 8..16;
+        for (position, &record) in (0u32..).zip(&self.drum_records) {
+            for key in (0..KEY_COUNT).filter(|key| record & 1 << key != 0) {
+                let drum = key / 2;
+                let channel =
+                    if let Some(&(_, channel)) = drum_channels.iter().find(|(d, _)| *d == drum) {
+                        channel
+                    } else {
+                        let Some(channel) = free_channels.next() else {
+                            continue;
+                        };
+                        song.channels[channel].instrument = drum;
+                        drum_channels.push((drum, channel));
+                        channel
+                    };
+                let mut db = volume_db(self.drum_volume, MAX_ENVELOPE);
+                if key % 2 == 1 {
+                    db += QUIET_DRUM_DB;
+                }
+                let event = event_at(&mut song.channels[channel].events, position);
+                // The loud variant wins when both are played
+                if event.pitch == PROPERTY_UNUSED || key % 2 == 0 {
+                    event.pitch = DRUM_PITCH;
+                    event.length = 1;
+                    event.volume = organya_volume(db);
+                }
+            }
+            if record & !KEY_MASK != 0 {
+                for &(_, ch) in &drum_channels {
+                    set_pan(event_at(&mut song.channels[ch].events, position), record);
+                }
+            }
+        }
+        (song, soundbank)
+    }
+
+    /// Add the notes of a melody track to `channels`
+    fn convert_track(&self, track: &PiyoTrack, channels: &[usize], song: &mut Song) {
+        let step_samples = u64::from(self.tempo_ms.max(1)) * u64::from(NOTE_RATE) / 1000;
+        let length = (u64::from(track.length) + step_samples / 2) / step_samples;
+        let length = u32::try_from(length.clamp(1, 255)).unwrap_or(1);
+        for (position, &record) in (0u32..).zip(&track.records) {
+            let keys = (0..KEY_COUNT).filter(|key| record & 1 << key != 0);
+            for (key, &ch) in keys.zip(channels) {
+                let events = &mut song.channels[ch].events;
+                // The new note replaces the envelope of the previous one
+                events.retain(|e| e.position <= position);
+                let pitch = (u16::from(track.octave) * 12 + u16::from(key)).min(95);
+                let note = event_at(events, position);
+                note.pitch = u8::try_from(pitch).unwrap_or(95);
+                note.length = u8::try_from(length).unwrap_or(u8::MAX);
+                let mut last_volume = PROPERTY_UNUSED;
+                for step in 0..length {
+                    let point = step as usize * PIYO_ENVELOPE_LEN / length as usize;
+                    let volume = organya_volume(volume_db(track.volume, track.envelope[point]));
+                    if volume != last_volume {
+                        event_at(events, position + step).volume = volume;
+                        last_volume = volume;
+                    }
+                }
+            }
+            if record & !KEY_MASK != 0 {
+                // Pans apply to the whole track
+                for &ch in channels {
+                    set_pan(event_at(&mut song.channels[ch].events, position), record);
+                }
+            }
+        }
+    }
+}
+
+/// Get the event at `position`, inserting an empty one if there is none
+fn event_at(events: &mut Vec<Event>, position: u32) -> &mut Event {
+    let index = events.partition_point(|e| e.position < position);
+    if events.get(index).is_none_or(|e| e.position != position) {
+        events.insert(index, Event::empty(position));
+    }
+    &mut events[index]
+}
+
+/// Set the pan of an event from the pan of a record, if it has one
+fn set_pan(event: &mut Event, record: u32) {
+    let pan = record >> 24;
+    if (1..=7).contains(&pan) {
+        // Pans from 1 to 7 map to every other Organya pan
+        event.pan = u8::try_from((pan - 1) * 2).unwrap_or(6);
+    }
+}
+
+/// The attenuation in hundredths of a decibel for a PiyoPiyo volume and envelope point
+fn volume_db(volume: u32, envelope: u8) -> i32 {
+    let volume = i32::try_from(volume.min(MAX_VOLUME)).unwrap_or(0);
+    let envelope = f64::from(envelope.min(MAX_ENVELOPE)) / f64::from(MAX_ENVELOPE);
+    // The envelope scales the amplitude
+    #[expect(clippy::cast_possible_truncation)]
+    let envelope_db = (2000.0 * envelope.log10()).max(-10000.0) as i32;
+    (volume - 300) * 8 + envelope_db
+}
+
+/// The Organya volume closest to an attenuation in hundredths of a decibel
+///
+/// No attenuation maps to the loudest Organya volume.
+fn organya_volume(db: i32) -> u8 {
+    // Each Organya volume step is 800 / 127 hundredths of a decibel
+    let volume = 254 + db * 127 / 800;
+    u8::try_from(volume.clamp(0, 254)).unwrap_or(0)
+}
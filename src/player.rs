@@ -1,13 +1,28 @@
 use {
     crate::{
-        Interpolation, OrgError, PROPERTY_UNUSED, read_cursor::ReadCursor, song::Song, sound::Sound,
+        Interpolation, OrgError, PROPERTY_UNUSED,
+        song::Song,
+        sound::Sound,
+        soundbank::{Samples, Soundbank},
+        timing::Timing,
     },
     std::{iter::zip, path::Path},
 };
 
 static SIZE_TABLE: [u16; 8] = [256, 256, 128, 128, 64, 32, 16, 8];
 static FREQ_TABLE: [u16; 12] = [262, 277, 294, 311, 330, 349, 370, 392, 415, 440, 466, 494];
-static PANNING_TABLE: [i16; 13] = [0, 43, 86, 129, 172, 215, 256, 297, 340, 383, 426, 469, 512];
+pub(crate) static PANNING_TABLE: [i16; 13] =
+    [0, 43, 86, 129, 172, 215, 256, 297, 340, 383, 426, 469, 512];
+
+/// The attenuation in hundredths of a decibel for an Organya volume
+pub(crate) fn volume_db(volume: u8) -> i16 {
+    (i16::from(volume) * 100 / 0x7f - 0xff) * 8
+}
+
+/// The pan in hundredths of a decibel for an Organya pan. Negative values pan to the left.
+pub(crate) fn pan_db(pan: u8) -> i16 {
+    (PANNING_TABLE[usize::from(pan)] - 0x100) * 10
+}
 
 type SndPair = [Sound; 2];
 
@@ -38,8 +53,6 @@ struct Percussion {
     sound: Sound,
 }
 
-pub type WaveData = Vec<i8>;
-
 /// Organya music player
 pub struct Player {
     song: Song,
@@ -51,8 +64,8 @@ pub struct Player {
     percussions: [Percussion; 8],
     volume: f32,
     sample_rate: u16,
-    melody_wave_data: [i8; 25_600],
-    percussion_wave_data: [WaveData; 42],
+    soundbank: Soundbank,
+    effects: Vec<Sound>,
 }
 
 impl Default for Player {
@@ -67,37 +80,44 @@ impl Default for Player {
             percussions: Default::default(),
             volume: Default::default(),
             sample_rate: Default::default(),
-            melody_wave_data: [0; _],
-            percussion_wave_data: [const { WaveData::new() }; _],
+            soundbank: Soundbank::default(),
+            effects: Vec::new(),
         };
         this.position = 0;
         this.last_position = 0;
         this.samples_to_next_tick = 0.0f64;
         this.set_sample_rate(44_100);
         this.volume = 1.0;
+        this.reset_channels();
         for melody in &mut this.melodies {
+            melody.muted = false;
+        }
+        for perc in &mut this.percussions {
+            perc.muted = false;
+        }
+        this
+    }
+}
+
+impl Player {
+    const fn reset_channels(&mut self) {
+        let mut i = 0;
+        while i < 8 {
+            let melody = &mut self.melodies[i];
             melody.pitch = PROPERTY_UNUSED;
             melody.volume = 200;
             melody.pan = 6;
             melody.index = 0;
             melody.ticks = 0;
             melody.alt = 0;
-            melody.muted = false;
-        }
-        for perc in &mut this.percussions {
+            let perc = &mut self.percussions[i];
             perc.pitch = PROPERTY_UNUSED;
             perc.volume = 200;
             perc.pan = 6;
             perc.index = 0;
-            perc.muted = false;
+            i += 1;
         }
-        this.melody_wave_data.fill(0);
-        this.percussion_wave_data.fill_with(WaveData::new);
-        this
     }
-}
-
-impl Player {
     fn load_instruments(&mut self) {
         let ([lo, hi], []) = self.song.channels.as_chunks::<8>() else {
             unreachable!()
@@ -111,10 +131,10 @@ impl Player {
                 for ch in &mut *sound {
                     ch.init(sample_count, self.sample_rate, self.volume_ramp);
                 }
+                let wave = &self.soundbank.melody_waves[usize::from(chan.instrument)];
                 let mut wave_index = 0;
                 for k in 0..sample_count {
-                    let sample = self.melody_wave_data
-                        [(usize::from(chan.instrument) * 0x100).wrapping_add(wave_index)];
+                    let sample = wave.get(wave_index);
                     sound[1].data[k] = sample;
                     sound[0].data[k] = sample;
                     wave_index = wave_index.wrapping_add(0x100 / usize::from(SIZE_TABLE[j])) & 0xff;
@@ -122,11 +142,16 @@ impl Player {
             }
         }
         for (perc, ch) in zip(&mut self.percussions, hi) {
-            let percussion_data = &self.percussion_wave_data[usize::from(ch.instrument)];
+            // Unresolved drums play silence. See `unresolved_drums`.
+            let percussion_data = self
+                .soundbank
+                .drums
+                .get(usize::from(ch.instrument))
+                .unwrap_or(const { &Samples::I8(Vec::new()) });
             perc.sound
                 .init(percussion_data.len(), self.sample_rate, self.volume_ramp);
-            for (&src, dst) in zip(percussion_data, &mut perc.sound.data) {
-                *dst = src.wrapping_add(-128);
+            for (i, dst) in perc.sound.data.iter_mut().enumerate() {
+                *dst = percussion_data.get(i);
             }
         }
     }
@@ -147,30 +172,23 @@ impl Player {
         for perc in &mut self.percussions {
             perc.sound.write_sample(out, interpolation);
         }
+        for effect in &mut self.effects {
+            effect.write_sample(out, interpolation);
+        }
+        self.effects.retain(Sound::is_active);
         out[0] *= self.volume;
         out[1] *= self.volume;
     }
     /// Read a soundbank file, which contains the samples required for playback.
     ///
+    /// Both the classic 8 bit format and the extended format are accepted.
+    /// See [`Soundbank`] for details.
+    ///
     /// # Errors
     ///
-    /// Returns [`OrgError::Malformed`] if the bank data is too short.
+    /// Returns [`OrgError::Malformed`] if the bank data is too short, or otherwise invalid.
     pub fn read_soundbank(&mut self, bank_data: &[u8]) -> Result<(), OrgError> {
-        let len = self.melody_wave_data.len();
-        if bank_data.len() < len + 42 * 4 {
-            return Err(OrgError::Malformed);
-        }
-        self.melody_wave_data
-            .copy_from_slice(bytemuck::cast_slice(&bank_data[..len]));
-        let mut read = ReadCursor(&bank_data[len..]);
-        for wave_data in &mut self.percussion_wave_data {
-            let Some(len) = read.next_u32_le() else {
-                return Err(OrgError::Malformed);
-            };
-            if len != 0 {
-                *wave_data = bytemuck::cast_slice(read.next_n_bytes(len as usize)).to_vec();
-            }
-        }
+        self.set_soundbank(Soundbank::read(bank_data)?);
         Ok(())
     }
     /// Load a soundbank from a file. See [`Self::read_soundbank`].
@@ -178,11 +196,35 @@ impl Player {
     /// # Errors
     ///
     /// - Returns [`std::io::Error`] if reading the file failed.
-    /// - Returns [`OrgError::Malformed`] if the bank data is too short.
+    /// - Returns [`OrgError::Malformed`] if the bank data is too short, or otherwise invalid.
     pub fn load_soundbank_file(&mut self, file_path: &Path) -> Result<(), OrgError> {
         let buffer = std::fs::read(file_path)?;
         self.read_soundbank(&buffer)
     }
+    /// The soundbank used for playback
+    #[must_use]
+    pub const fn soundbank(&self) -> &Soundbank {
+        &self.soundbank
+    }
+    /// Replace the soundbank used for playback
+    ///
+    /// The instruments of the current song are resolved against the new soundbank.
+    pub fn set_soundbank(&mut self, soundbank: Soundbank) {
+        self.soundbank = soundbank;
+        self.load_instruments();
+    }
+    /// The drum channels of the current song whose instrument is out of range for the soundbank
+    ///
+    /// Yields `(channel index, instrument)` pairs. These channels play silence.
+    pub fn unresolved_drums(&self) -> impl Iterator<Item = (usize, u8)> {
+        self.song
+            .channels
+            .iter()
+            .enumerate()
+            .skip(8)
+            .filter(|(_, ch)| usize::from(ch.instrument) >= self.soundbank.drums.len())
+            .map(|(i, ch)| (i, ch.instrument))
+    }
 
     const fn set_sample_rate(&mut self, sample_rate: u16) {
         self.sample_rate = sample_rate;
@@ -191,6 +233,8 @@ impl Player {
 
     /// Reads Organya song data and seeks to the beginning
     ///
+    /// Drum instruments missing from the soundbank are reported by [`Self::unresolved_drums`].
+    ///
     /// # Errors
     ///
     /// Returns [`OrgError::Malformed`] if the data can't be interpreted as Organya.
@@ -212,6 +256,55 @@ impl Player {
         self.load_instruments();
         Ok(())
     }
+    /// The song being played
+    #[must_use]
+    pub const fn song(&self) -> &Song {
+        &self.song
+    }
+    /// Replace the song being played, and seek to the beginning
+    pub fn set_song(&mut self, song: Song) {
+        self.song = song;
+        self.seek(0);
+        self.load_instruments();
+    }
+    /// Play a sound effect once, mixed with the music
+    ///
+    /// The samples are played at 22050 Hz, like drums and [`PixTone`](crate::PixTone) sounds.
+    /// Any number of effects can play at the same time.
+    pub fn play_effect(&mut self, samples: &Samples) {
+        let mut sound = Sound::default();
+        sound.init(samples.len(), self.sample_rate, self.volume_ramp);
+        for (i, dst) in sound.data.iter_mut().enumerate() {
+            *dst = samples.get(i);
+        }
+        sound.play(false);
+        self.effects.push(sound);
+    }
+    /// Stop all sound effects started by [`Self::play_effect`]
+    pub fn stop_effects(&mut self) {
+        self.effects.clear();
+    }
+    /// Seek to the beginning of the song, and reset all playback state
+    ///
+    /// The output after this is the same as if the song was just loaded into a new player.
+    /// Sound effects are stopped.
+    pub fn restart(&mut self) {
+        self.effects.clear();
+        self.reset_channels();
+        self.samples_to_next_tick = 0.0;
+        self.seek(0);
+        self.load_instruments();
+    }
+    /// The output sample rate in Hz
+    #[must_use]
+    pub const fn sample_rate(&self) -> u16 {
+        self.sample_rate
+    }
+    /// The timing of the current song at the output sample rate
+    #[must_use]
+    pub fn timing(&self) -> Timing {
+        Timing::new(&self.song, self.sample_rate)
+    }
 
     fn seek(&mut self, position: u32) {
         self.last_position = position;
@@ -271,17 +364,12 @@ impl Player {
             }
             if event.volume != PROPERTY_UNUSED {
                 perc.volume = event.volume;
-                perc.sound.set_volume(
-                    (i16::from(perc.volume) * 100 / 0x7f - 0xff) * 8,
-                    self.volume_ramp,
-                );
+                perc.sound
+                    .set_volume(volume_db(perc.volume), self.volume_ramp);
             }
             if event.pan != PROPERTY_UNUSED {
                 perc.pan = event.pan;
-                perc.sound.set_pan(
-                    (PANNING_TABLE[usize::from(perc.pan)] - 0x100) * 10,
-                    self.volume_ramp,
-                );
+                perc.sound.set_pan(pan_db(perc.pan), self.volume_ramp);
             }
             perc.index += 1;
         }
@@ -319,14 +407,14 @@ impl Player {
                     if event.volume != PROPERTY_UNUSED {
                         melody.volume = event.volume;
                         if melody.pitch != PROPERTY_UNUSED {
-                            let vol = (i16::from(melody.volume) * 100 / 0x7f - 0xff) * 8;
+                            let vol = volume_db(melody.volume);
                             melody.pitch_alt_sound().set_volume(vol, self.volume_ramp);
                         }
                     }
                     if event.pan != PROPERTY_UNUSED {
                         melody.pan = event.pan;
                         if melody.pitch != PROPERTY_UNUSED {
-                            let pan = (PANNING_TABLE[usize::from(melody.pan)] - 0x100) * 10;
+                            let pan = pan_db(melody.pan);
                             melody.pitch_alt_sound().set_pan(pan, self.volume_ramp);
                         }
                     }
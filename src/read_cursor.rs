@@ -6,10 +6,10 @@ impl<'a> ReadCursor<'a> {
         self.0 = next;
         Some(bytes)
     }
-    pub const fn next_n_bytes(&mut self, n: usize) -> &'a [u8] {
-        let (bytes, next) = self.0.split_at(n);
+    pub fn next_n_bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (bytes, next) = self.0.split_at_checked(n)?;
         self.0 = next;
-        bytes
+        Some(bytes)
     }
     pub fn next_u8(&mut self) -> Option<u8> {
         let (&byte, next) = self.0.split_first()?;
@@ -22,13 +22,4 @@ impl<'a> ReadCursor<'a> {
     pub fn next_u32_le(&mut self) -> Option<u32> {
         self.next_bytes().copied().map(u32::from_le_bytes)
     }
-    pub fn u8_at(&self, offset: usize) -> u8 {
-        self.0[offset]
-    }
-    pub fn u32_le_at(&self, offset: usize) -> u32 {
-        u32::from_le_bytes(*self.0[offset..].first_chunk().unwrap())
-    }
-    pub fn skip(&mut self, amount: usize) {
-        self.0 = &self.0[amount..];
-    }
 }
@@ -0,0 +1,101 @@
+use crate::{Interpolation, player::Player};
+
+/// A song rendered as an intro and a seamlessly looping body
+///
+/// Both parts are interleaved stereo 32 bit floating point samples at the player's sample rate.
+pub struct LoopRender {
+    /// The part of the song from the beginning to the repeat start
+    pub intro: Vec<f32>,
+    /// The part of the song from the repeat start to the repeat end
+    ///
+    /// This is rendered from the second time the loop plays, so it starts with the tails of
+    /// the notes that carry across the loop boundary, and repeats seamlessly. Notes carrying
+    /// over from the intro are not part of it.
+    pub body: Vec<f32>,
+}
+
+impl Player {
+    /// Render the current song from the beginning, as an intro and a looping body
+    ///
+    /// This is suited for game engines that play an intro followed by a looping part.
+    pub fn render_loop(&mut self, interpolation: Interpolation) -> LoopRender {
+        self.restart();
+        let song = self.song();
+        let timing = self.timing();
+        let loop_ticks = u64::from(song.repeat_end.saturating_sub(song.repeat_start));
+        let intro_ticks = u64::from(song.repeat_start.min(song.repeat_end));
+        let loop_frame = |pass: u64| timing.tick_to_frame(intro_ticks + loop_ticks * pass);
+        // The intro and both passes are rendered continuously, then the first pass is dropped
+        let mut intro = vec![0.0; frames_to_len(loop_frame(2))];
+        self.write_next(&mut intro, interpolation);
+        let body = intro.split_off(frames_to_len(loop_frame(1)));
+        intro.truncate(frames_to_len(loop_frame(0)));
+        LoopRender { intro, body }
+    }
+}
+
+/// The interleaved stereo buffer length for `frames` frames
+fn frames_to_len(frames: u64) -> usize {
+    usize::try_from(frames * 2).expect("Render too large to fit in memory")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            song::{Event, Song},
+            test_util::{render, soundbank},
+        },
+    };
+
+    /// A song whose intro and loop both have notes carrying past their end
+    fn carrying_song() -> Song {
+        let event = |position, pitch, length| Event {
+            position,
+            pitch,
+            length,
+            volume: 200,
+            pan: 6,
+        };
+        let mut song = Song {
+            // A whole number of frames per tick, so every pass is as long as the others
+            tempo_ms: 20,
+            repeat_start: 4,
+            repeat_end: 20,
+            ..Song::default()
+        };
+        song.channels[0].events = vec![
+            event(4, 48, 2),
+            event(10, 50, 3),
+            event(14, 52, 2),
+            event(19, 36, 6),
+        ];
+        song.channels[1].events = vec![event(0, 60, 10)];
+        song.channels[8].instrument = 41;
+        // Drums keep their sub-sample phase when played again, pitch 55 plays at exactly the
+        // sample rate so it doesn't drift from one pass to the next
+        song.channels[8].events = vec![event(2, 55, 1), event(18, 55, 1)];
+        song
+    }
+
+    #[test]
+    fn body_loops_seamlessly() {
+        let song = carrying_song();
+        let mut player = Player::default();
+        player.set_soundbank(soundbank());
+        player.set_song(song.clone());
+        let LoopRender { intro, body } = player.render_loop(Interpolation::Lagrange);
+        let timing = player.timing();
+        let len = |tick: u32| frames_to_len(timing.tick_to_frame(tick.into()));
+        let (start, end) = (song.repeat_start, song.repeat_end);
+        let loop_ticks = end - start;
+        let passes = render(&song, len(end + 2 * loop_ticks) / 2);
+        assert!(intro == passes[..len(start)]);
+        // The body is the second pass, and the third pass that follows it is the same
+        assert!(body == passes[len(end)..len(end + loop_ticks)]);
+        assert!(body == passes[len(end + loop_ticks)..]);
+        // The first pass differs, it has tails of the intro but none from the loop
+        assert!(body != passes[len(start)..len(end)]);
+    }
+}
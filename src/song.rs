@@ -1,12 +1,13 @@
 use {
-    crate::{OrgError, PROPERTY_UNUSED, read_cursor::ReadCursor},
+    crate::{OrgError, view::SongView},
     std::path::Path,
 };
 
 /// An event that happens in the song
 ///
 /// Some properties are optional. If their value is [`PROPERTY_UNUSED`], they are ignored.
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     /// When in the song this event happens
     pub position: u32,
@@ -24,7 +25,8 @@ pub struct Event {
 /// with other channels to produce the final output.
 ///
 /// There are 8 melody channels, and 8 drum channels.
-#[derive(Default)]
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel {
     /// The index of the instrument in the instrument bank
     ///
@@ -37,6 +39,8 @@ pub struct Channel {
 }
 
 /// An Organya song
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Song {
     /// Tempo of the song
     pub tempo_ms: u16,
@@ -87,57 +91,14 @@ impl Default for Song {
 impl Song {
     /// Read the song from raw bytes
     ///
-    /// # Panics
+    /// See [`SongView`] to read it without copying the events.
     ///
-    /// - May panic on I/O errors
+    /// # Errors
+    ///
+    /// Returns [`OrgError::Malformed`] if the data can't be interpreted as Organya. The song
+    /// is left unchanged.
     pub fn read(&mut self, data: &[u8]) -> Result<(), OrgError> {
-        if data.len() < 114 {
-            return Err(OrgError::Malformed);
-        }
-        let mut read = ReadCursor(data);
-        if read.next_bytes() != Some(b"Org-") {
-            return Err(OrgError::Malformed);
-        }
-        let version = ((read.next_u8().unwrap()) - b'0') * 10 + ((read.next_u8().unwrap()) - b'0');
-        if !(1..=3).contains(&version) {
-            return Err(OrgError::Malformed);
-        }
-        self.tempo_ms = read.next_u16_le().unwrap();
-        self.beats_per_measure = read.next_u8().unwrap();
-        self.steps_per_beat = read.next_u8().unwrap();
-        self.repeat_start = read.next_u32_le().unwrap();
-        self.repeat_end = read.next_u32_le().unwrap();
-        for (i, ch) in self.channels.iter_mut().enumerate() {
-            ch.finetune = read.next_u16_le().unwrap();
-            ch.instrument = read.next_u8().unwrap();
-            let pizzicato = read.next_u8().unwrap();
-            ch.pizzicato = if version > 1 { pizzicato == 1 } else { false };
-            if i < 8 && ch.instrument >= 100 || i >= 8 && ch.instrument >= 42 {
-                ch.instrument = 0;
-            }
-            let event_count = read.next_u16_le().unwrap();
-            ch.events = vec![Event::default(); usize::from(event_count)];
-        }
-        for ch in &mut self.channels {
-            let len = ch.events.len();
-            for (j, evt) in ch.events.iter_mut().enumerate() {
-                evt.position = read.u32_le_at(j * 4);
-                evt.pitch = read.u8_at((len * 4) + j);
-                evt.length = read.u8_at((len * 5) + j);
-                evt.volume = read.u8_at((len * 6) + j);
-                evt.pan = read.u8_at((len * 7) + j);
-                if evt.pitch >= 96 {
-                    evt.pitch = PROPERTY_UNUSED;
-                }
-                if evt.length == 0 {
-                    evt.length = 1;
-                }
-                if evt.pan > 12 && evt.pan != PROPERTY_UNUSED {
-                    evt.pan = 6;
-                }
-            }
-            read.skip(len * 8);
-        }
+        *self = SongView::new(data)?.to_song();
         Ok(())
     }
 
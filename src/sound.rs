@@ -2,7 +2,7 @@ use crate::Interpolation;
 
 #[derive(Clone, Default)]
 pub struct Sound {
-    pub(crate) data: Vec<i8>,
+    pub(crate) data: Vec<f32>,
     samples: [f32; 8],
     position: usize,
     sub_position: f32,
@@ -25,7 +25,7 @@ pub struct Sound {
 
 impl Sound {
     pub(crate) fn init(&mut self, sample_count: usize, sample_rate: u16, volume_ramp: u16) {
-        self.data = vec![0; sample_count];
+        self.data = vec![0.0; sample_count];
         self.samples.fill(0.0);
         self.position = 0;
         self.sub_position = 0.0;
@@ -102,6 +102,11 @@ impl Sound {
         self.looping = looping;
     }
 
+    /// Whether the sound is playing, or fading out after it stopped
+    pub(crate) const fn is_active(&self) -> bool {
+        self.playing || self.silence_timer > 0
+    }
+
     pub(crate) const fn stop(&mut self) {
         self.playing = false;
         self.silence_timer = 8;
@@ -112,7 +117,7 @@ impl Sound {
         [out_l, out_r]: &mut [f32; 2],
         interpolation: Interpolation,
     ) {
-        if !(self.playing || self.silence_timer > 0) {
+        if !self.is_active() {
             return;
         }
         if self.volume_ticks > 0 {
@@ -139,14 +144,12 @@ impl Sound {
                 let sample = &mut self.samples[usize::try_from(self.ring).unwrap()];
                 if self.playing {
                     if self.looping {
-                        *sample = f32::from(
-                            (&self.data)[(last_position + i).wrapping_rem(self.data.len())],
-                        ) / 128.0;
+                        *sample = self.data[(last_position + i).wrapping_rem(self.data.len())];
                     } else {
                         *sample = if last_position + i >= self.data.len() {
                             0.0
                         } else {
-                            f32::from((self.data)[last_position + i]) / 128.0
+                            self.data[last_position + i]
                         };
                     }
                 } else {
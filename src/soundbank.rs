@@ -0,0 +1,336 @@
+use {
+    crate::{OrgError, read_cursor::ReadCursor},
+    std::path::Path,
+};
+
+/// Number of melody waveforms in a soundbank
+pub const MELODY_WAVE_COUNT: usize = 100;
+/// Number of samples in a single melody waveform
+pub const MELODY_WAVE_LEN: usize = 256;
+/// Number of drums in the original Organya soundbank
+///
+/// Soundbanks can have any number of drums, but this is how many the original games use.
+pub const CLASSIC_DRUM_COUNT: usize = 42;
+
+/// Magic bytes at the start of an extended soundbank
+const EXTENDED_MAGIC: &[u8; 8] = b"ORGBANK\x1A";
+/// The extended soundbank version written by [`Soundbank::write_extended`]
+const EXTENDED_VERSION: u8 = 1;
+
+/// Storage format of samples
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SampleFormat {
+    /// Signed 8 bit integer
+    I8,
+    /// Signed 16 bit integer
+    I16,
+    /// 32 bit floating point, in the `-1.0..=1.0` range
+    F32,
+}
+
+impl SampleFormat {
+    const fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::I8),
+            1 => Some(Self::I16),
+            2 => Some(Self::F32),
+            _ => None,
+        }
+    }
+    const fn to_u8(self) -> u8 {
+        match self {
+            Self::I8 => 0,
+            Self::I16 => 1,
+            Self::F32 => 2,
+        }
+    }
+    const fn size(self) -> usize {
+        match self {
+            Self::I8 => 1,
+            Self::I16 => 2,
+            Self::F32 => 4,
+        }
+    }
+}
+
+/// The samples of a melody waveform or a drum
+#[derive(Clone, Debug)]
+pub enum Samples {
+    /// Signed 8 bit samples
+    I8(Vec<i8>),
+    /// Signed 16 bit samples
+    I16(Vec<i16>),
+    /// 32 bit floating point samples, in the `-1.0..=1.0` range
+    F32(Vec<f32>),
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Self::I8(Vec::new())
+    }
+}
+
+impl Samples {
+    /// The storage format of the samples
+    #[must_use]
+    pub const fn format(&self) -> SampleFormat {
+        match self {
+            Self::I8(_) => SampleFormat::I8,
+            Self::I16(_) => SampleFormat::I16,
+            Self::F32(_) => SampleFormat::F32,
+        }
+    }
+    /// The number of samples
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        match self {
+            Self::I8(samples) => samples.len(),
+            Self::I16(samples) => samples.len(),
+            Self::F32(samples) => samples.len(),
+        }
+    }
+    /// Whether there are no samples
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// The sample at `index`, normalized to the `-1.0..=1.0` range
+    ///
+    /// Returns silence if `index` is out of bounds.
+    #[must_use]
+    pub fn get(&self, index: usize) -> f32 {
+        match self {
+            Self::I8(samples) => samples.get(index).map_or(0.0, |&s| f32::from(s) / 128.0),
+            Self::I16(samples) => samples.get(index).map_or(0.0, |&s| f32::from(s) / 32768.0),
+            Self::F32(samples) => samples.get(index).copied().unwrap_or(0.0),
+        }
+    }
+    /// Convert the samples to another storage format
+    ///
+    /// Converting to a narrower format quantizes the samples.
+    #[must_use]
+    pub fn to_format(&self, format: SampleFormat) -> Self {
+        if self.format() == format {
+            return self.clone();
+        }
+        let normalized = (0..self.len()).map(|i| self.get(i));
+        // The samples are clamped to the target range before casting
+        #[expect(clippy::cast_possible_truncation)]
+        match format {
+            SampleFormat::I8 => Self::I8(
+                normalized
+                    .map(|s| (s * 128.0).round().clamp(-128.0, 127.0) as i8)
+                    .collect(),
+            ),
+            SampleFormat::I16 => Self::I16(
+                normalized
+                    .map(|s| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16)
+                    .collect(),
+            ),
+            SampleFormat::F32 => Self::F32(normalized.collect()),
+        }
+    }
+    fn read(read: &mut ReadCursor, format: SampleFormat, count: usize) -> Option<Self> {
+        let bytes = read.next_n_bytes(count.checked_mul(format.size())?)?;
+        Some(match format {
+            SampleFormat::I8 => Self::I8(bytemuck::cast_slice(bytes).to_vec()),
+            SampleFormat::I16 => Self::I16(
+                bytes
+                    .as_chunks()
+                    .0
+                    .iter()
+                    .map(|&b| i16::from_le_bytes(b))
+                    .collect(),
+            ),
+            SampleFormat::F32 => Self::F32(
+                bytes
+                    .as_chunks()
+                    .0
+                    .iter()
+                    .map(|&b| f32::from_le_bytes(b))
+                    .collect(),
+            ),
+        })
+    }
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::I8(samples) => out.extend_from_slice(bytemuck::cast_slice(samples)),
+            Self::I16(samples) => out.extend(samples.iter().flat_map(|s| s.to_le_bytes())),
+            Self::F32(samples) => out.extend(samples.iter().flat_map(|s| s.to_le_bytes())),
+        }
+    }
+}
+
+/// The instrument samples used for playing Organya songs
+///
+/// There are two file formats:
+///
+/// - The classic format is 8 bit only. It consists of the melody waveforms as signed bytes,
+///   followed by a length prefixed list of unsigned 8 bit drum samples, which extends to the
+///   end of the data. There are at least [`CLASSIC_DRUM_COUNT`] drums. Past those, trailing
+///   bytes that don't form a complete drum, or a drum of length 0, are padding and are ignored.
+/// - The extended format starts with a magic number and a version, and stores every sample
+///   in the same [`SampleFormat`].
+#[derive(Clone)]
+pub struct Soundbank {
+    /// The melody waveforms, each one [`MELODY_WAVE_LEN`] samples long
+    pub melody_waves: [Samples; MELODY_WAVE_COUNT],
+    /// The drum samples, played back at 22050 Hz at their default pitch
+    pub drums: Vec<Samples>,
+}
+
+impl Default for Soundbank {
+    fn default() -> Self {
+        Self {
+            melody_waves: std::array::from_fn(|_| Samples::I8(vec![0; MELODY_WAVE_LEN])),
+            drums: vec![Samples::default(); CLASSIC_DRUM_COUNT],
+        }
+    }
+}
+
+impl Soundbank {
+    /// Read a soundbank in either the classic or the extended format
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::Malformed`] if the bank data is too short, or otherwise invalid.
+    pub fn read(data: &[u8]) -> Result<Self, OrgError> {
+        match data.strip_prefix(EXTENDED_MAGIC) {
+            Some(data) => Self::read_extended(data),
+            None => Self::read_classic(data),
+        }
+        .ok_or(OrgError::Malformed)
+    }
+    /// Load a soundbank from a file. See [`Self::read`].
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`std::io::Error`] if reading the file failed.
+    /// - Returns [`OrgError::Malformed`] if the bank data is too short, or otherwise invalid.
+    pub fn load_file(file_path: &Path) -> Result<Self, OrgError> {
+        let buffer = std::fs::read(file_path)?;
+        Self::read(&buffer)
+    }
+    fn read_classic(data: &[u8]) -> Option<Self> {
+        let mut this = Self::default();
+        let mut read = ReadCursor(data);
+        for wave in &mut this.melody_waves {
+            *wave = Samples::read(&mut read, SampleFormat::I8, MELODY_WAVE_LEN)?;
+        }
+        this.drums.clear();
+        let mut next_drum = || {
+            let len = read.next_u32_le()?;
+            let bytes: &[i8] = bytemuck::cast_slice(read.next_n_bytes(len as usize)?);
+            // Classic drum samples are unsigned
+            Some(Samples::I8(
+                bytes.iter().map(|s| s.wrapping_add(-128)).collect(),
+            ))
+        };
+        for _ in 0..CLASSIC_DRUM_COUNT {
+            this.drums.push(next_drum()?);
+        }
+        // Padding, or extra bytes, end the list
+        this.drums
+            .extend(std::iter::from_fn(next_drum).take_while(|drum| !drum.is_empty()));
+        Some(this)
+    }
+    fn read_extended(data: &[u8]) -> Option<Self> {
+        let mut read = ReadCursor(data);
+        if read.next_u8()? != EXTENDED_VERSION {
+            return None;
+        }
+        let format = SampleFormat::from_u8(read.next_u8()?)?;
+        let drum_count = read.next_u16_le()?;
+        let mut this = Self::default();
+        for wave in &mut this.melody_waves {
+            *wave = Samples::read(&mut read, format, MELODY_WAVE_LEN)?;
+        }
+        this.drums.clear();
+        for _ in 0..drum_count {
+            let len = read.next_u32_le()?;
+            this.drums
+                .push(Samples::read(&mut read, format, len as usize)?);
+        }
+        Some(this)
+    }
+    /// Write the soundbank in the classic 8 bit format
+    ///
+    /// Samples stored in wider formats are quantized to 8 bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a drum has more than [`u32::MAX`] samples.
+    #[must_use]
+    pub fn write_classic(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for wave in &self.melody_waves {
+            write_wave(wave, SampleFormat::I8, &mut out);
+        }
+        for drum in &self.drums {
+            let Samples::I8(samples) = drum.to_format(SampleFormat::I8) else {
+                unreachable!()
+            };
+            out.extend_from_slice(&u32::try_from(samples.len()).unwrap().to_le_bytes());
+            out.extend(
+                samples
+                    .iter()
+                    .map(|s| s.wrapping_add(-128).to_ne_bytes()[0]),
+            );
+        }
+        out
+    }
+    /// Write the soundbank in the extended format, with every sample stored as `format`
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than [`u16::MAX`] drums, or a drum has more than [`u32::MAX`]
+    /// samples.
+    #[must_use]
+    pub fn write_extended(&self, format: SampleFormat) -> Vec<u8> {
+        let mut out = EXTENDED_MAGIC.to_vec();
+        out.push(EXTENDED_VERSION);
+        out.push(format.to_u8());
+        out.extend_from_slice(&u16::try_from(self.drums.len()).unwrap().to_le_bytes());
+        for wave in &self.melody_waves {
+            write_wave(wave, format, &mut out);
+        }
+        for drum in &self.drums {
+            out.extend_from_slice(&u32::try_from(drum.len()).unwrap().to_le_bytes());
+            drum.to_format(format).write(&mut out);
+        }
+        out
+    }
+}
+
+/// Write a melody wave, padded or truncated to [`MELODY_WAVE_LEN`] samples
+fn write_wave(wave: &Samples, format: SampleFormat, out: &mut Vec<u8>) {
+    let mut wave = wave.to_format(format);
+    match &mut wave {
+        Samples::I8(samples) => samples.resize(MELODY_WAVE_LEN, 0),
+        Samples::I16(samples) => samples.resize(MELODY_WAVE_LEN, 0),
+        Samples::F32(samples) => samples.resize(MELODY_WAVE_LEN, 0.0),
+    }
+    wave.write(out);
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::soundbank};
+
+    #[test]
+    fn classic_trailing_bytes() {
+        let bank = soundbank();
+        let data = bank.write_classic();
+        let drums = |data: &[u8]| Soundbank::read(data).map(|b| b.drums.len());
+        assert_eq!(drums(&data).unwrap(), CLASSIC_DRUM_COUNT);
+        for padding in [&[0u8; 3][..], &[0; 4], &[0; 9], &[0xFF; 6], &[1, 0, 0, 0]] {
+            let padded = [&data[..], padding].concat();
+            assert_eq!(drums(&padded).unwrap(), CLASSIC_DRUM_COUNT);
+        }
+        // Extra complete drums are kept
+        let extra = [&data[..], &[2, 0, 0, 0, 0x80, 0x80]].concat();
+        assert_eq!(drums(&extra).unwrap(), CLASSIC_DRUM_COUNT + 1);
+        // The last base drum is cut
+        assert!(drums(&data[..data.len() - 1]).is_err());
+    }
+}
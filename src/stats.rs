@@ -0,0 +1,104 @@
+use {
+    crate::{PROPERTY_UNUSED, song::Song},
+    std::{ops::RangeInclusive, time::Duration},
+};
+
+/// Summary of a song, made by [`Song::stats`]
+///
+/// Only events before the repeat end are counted, the player never reaches the others.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SongStats {
+    /// Statistics of each of the 16 channels
+    pub channels: [ChannelStats; 16],
+    /// Melody instruments of the channels that play notes, sorted
+    pub melody_instruments: Vec<u8>,
+    /// Drum instruments of the channels that play notes, sorted
+    pub drum_instruments: Vec<u8>,
+    /// Number of measures up to the repeat end
+    pub measures: u32,
+    /// Number of measures where at least one note starts
+    pub occupied_measures: u32,
+    /// Ticks before the repeat start
+    pub intro_ticks: u32,
+    /// Ticks from the repeat start to the repeat end
+    pub loop_ticks: u32,
+    /// How long the intro plays
+    pub intro_duration: Duration,
+    /// How long one loop plays
+    pub loop_duration: Duration,
+    /// Tempo in beats per minute
+    pub bpm: f64,
+}
+
+/// Summary of a channel, part of [`SongStats`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Number of notes
+    pub notes: usize,
+    /// Lowest and highest pitch of the notes, if there are any
+    pub pitch_range: Option<RangeInclusive<u8>>,
+    /// The instrument of the channel
+    pub instrument: u8,
+}
+
+impl Song {
+    /// Summarize the song
+    #[must_use]
+    pub fn stats(&self) -> SongStats {
+        let measure_len = self.measure_len();
+        let measures = self.repeat_end.div_ceil(measure_len);
+        let mut occupied = Vec::new();
+        let channels = std::array::from_fn(|i| {
+            let ch = &self.channels[i];
+            let mut stats = ChannelStats {
+                instrument: ch.instrument,
+                ..ChannelStats::default()
+            };
+            let notes = ch
+                .events
+                .iter()
+                .filter(|e| e.pitch != PROPERTY_UNUSED && e.position < self.repeat_end);
+            for note in notes {
+                stats.notes += 1;
+                stats.pitch_range = Some(match stats.pitch_range.take() {
+                    Some(range) => {
+                        (*range.start()).min(note.pitch)..=(*range.end()).max(note.pitch)
+                    }
+                    None => note.pitch..=note.pitch,
+                });
+                occupied.push(note.position / measure_len);
+            }
+            stats
+        });
+        let instruments = |channels: &[ChannelStats]| {
+            let mut instruments: Vec<u8> = channels
+                .iter()
+                .filter(|ch| ch.notes > 0)
+                .map(|ch| ch.instrument)
+                .collect();
+            instruments.sort_unstable();
+            instruments.dedup();
+            instruments
+        };
+        occupied.sort_unstable();
+        occupied.dedup();
+        let (melody, drums) = channels.split_at(8);
+        let intro_ticks = self.repeat_start.min(self.repeat_end);
+        let loop_ticks = self.repeat_end - intro_ticks;
+        let duration =
+            |ticks: u32| Duration::from_millis(u64::from(ticks) * u64::from(self.tempo_ms));
+        SongStats {
+            melody_instruments: instruments(melody),
+            drum_instruments: instruments(drums),
+            measures,
+            // There are at most as many as measures
+            occupied_measures: u32::try_from(occupied.len()).unwrap_or(measures),
+            intro_ticks,
+            loop_ticks,
+            intro_duration: duration(intro_ticks),
+            loop_duration: duration(loop_ticks),
+            bpm: 60_000.0 / (f64::from(self.tempo_ms) * f64::from(self.steps_per_beat)).max(1.0),
+            channels,
+        }
+    }
+}
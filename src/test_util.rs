@@ -0,0 +1,124 @@
+//! Songs and soundbanks made up for tests
+
+use crate::{
+    Interpolation, PROPERTY_UNUSED,
+    player::Player,
+    song::{Event, Song},
+    soundbank::{CLASSIC_DRUM_COUNT, MELODY_WAVE_LEN, Samples, Soundbank},
+};
+
+/// A small deterministic pseudo random number generator
+pub struct Rng(u64);
+
+impl Rng {
+    pub const fn new(seed: u64) -> Self {
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+    /// A number in `0..n`
+    pub fn below(&mut self, n: u32) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        u32::try_from((self.0 >> 33) % u64::from(n)).unwrap()
+    }
+    pub fn byte_below(&mut self, n: u8) -> u8 {
+        u8::try_from(self.below(n.into())).unwrap()
+    }
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// A soundbank with a distinct wave per melody instrument, and decaying noise drums
+pub fn soundbank() -> Soundbank {
+    let mut rng = Rng::new(0);
+    let melody_waves = std::array::from_fn(|i| {
+        Samples::I8(
+            (0..MELODY_WAVE_LEN)
+                .map(|j| {
+                    let x = u8::try_from((j * (i % 7 + 1)) % 256).unwrap();
+                    x.cast_signed() / 2
+                })
+                .collect(),
+        )
+    });
+    let drums = (0..CLASSIC_DRUM_COUNT)
+        .map(|i| {
+            let len = 200 + i * 150;
+            Samples::I8(
+                (0..len)
+                    .map(|j| {
+                        let amplitude = 100 * (len - j) / len;
+                        let amplitude = i8::try_from(amplitude).unwrap();
+                        i8::try_from(rng.below(3)).unwrap().wrapping_sub(1) * amplitude
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    Soundbank {
+        melody_waves,
+        drums,
+    }
+}
+
+/// A random, normalized song with many patterns the linter looks for
+pub fn random_song(seed: u64) -> Song {
+    let mut rng = Rng::new(seed);
+    let mut song = Song {
+        tempo_ms: [2, 3, 20, 45][usize::try_from(rng.below(4)).unwrap()],
+        repeat_start: rng.below(24),
+        repeat_end: 24 + rng.below(40),
+        ..Song::default()
+    };
+    for (i, ch) in song.channels.iter_mut().enumerate() {
+        if rng.chance(25) {
+            continue;
+        }
+        ch.instrument = if i < 8 {
+            rng.byte_below(100)
+        } else {
+            rng.byte_below(u8::try_from(CLASSIC_DRUM_COUNT).unwrap())
+        };
+        ch.pizzicato = rng.chance(20);
+        let mut position = rng.below(4);
+        let (mut volume, mut pan) = (200, 6);
+        while position < song.repeat_end + 8 {
+            if rng.chance(30) {
+                volume = 100 + rng.byte_below(150);
+            }
+            if rng.chance(20) {
+                pan = rng.byte_below(13);
+            }
+            ch.events.push(Event {
+                position,
+                // Higher drum pitches overflow the frequency
+                pitch: if rng.chance(60) {
+                    rng.byte_below(if i < 8 { 96 } else { 80 })
+                } else {
+                    PROPERTY_UNUSED
+                },
+                length: 1 + rng.byte_below(12),
+                volume: if rng.chance(60) {
+                    volume
+                } else {
+                    PROPERTY_UNUSED
+                },
+                pan: if rng.chance(40) { pan } else { PROPERTY_UNUSED },
+            });
+            position += 1 + rng.below(5);
+        }
+    }
+    song
+}
+
+/// The first `frames` stereo frames the player outputs for `song`
+pub fn render(song: &Song, frames: usize) -> Vec<f32> {
+    let mut player = Player::default();
+    player.set_soundbank(soundbank());
+    player.set_song(song.clone());
+    let mut out = vec![0.0; frames * 2];
+    player.write_next(&mut out, Interpolation::Lagrange);
+    out
+}
@@ -0,0 +1,300 @@
+use {
+    crate::{
+        OrgError, PROPERTY_UNUSED, SyntaxError,
+        song::{Event, Song},
+    },
+    std::{fmt::Write, str::FromStr},
+};
+
+/// First line of the text format, with its version
+const TEXT_HEADER: &str = "organya-text 1";
+/// How unused event properties are written
+const UNUSED: &str = "-";
+
+impl Song {
+    /// Write the song in a line based text format, suited for diffing and merging
+    ///
+    /// The output starts with the header fields, then each of the 16 channels with its
+    /// settings, followed by one line per event: position, pitch, length, volume and pan. Unused
+    /// properties are written as `-`. [`Self::from_text`] reads it back to an identical song.
+    ///
+    /// ```text
+    /// organya-text 1
+    /// tempo_ms 125
+    /// beats_per_measure 4
+    /// steps_per_beat 4
+    /// repeat_start 0
+    /// repeat_end 64
+    ///
+    /// channel 0 instrument 0 finetune 1000 pizzicato 0
+    /// 0 36 4 200 6
+    /// 4 - 1 150 -
+    /// ```
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        // Writing to a string can't fail
+        let _ = self.write_text(&mut out);
+        out
+    }
+    fn write_text(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(out, "{TEXT_HEADER}")?;
+        writeln!(out, "tempo_ms {}", self.tempo_ms)?;
+        writeln!(out, "beats_per_measure {}", self.beats_per_measure)?;
+        writeln!(out, "steps_per_beat {}", self.steps_per_beat)?;
+        writeln!(out, "repeat_start {}", self.repeat_start)?;
+        writeln!(out, "repeat_end {}", self.repeat_end)?;
+        for (i, ch) in self.channels.iter().enumerate() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "channel {i} instrument {} finetune {} pizzicato {}",
+                ch.instrument,
+                ch.finetune,
+                u8::from(ch.pizzicato)
+            )?;
+            for evt in &ch.events {
+                writeln!(out, "{}", EventLine(evt))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a song written by [`Self::to_text`]
+    ///
+    /// Blank lines are ignored, and `#` starts a comment that runs to the end of the line.
+    /// Header fields that are missing keep their default values, and so do channels that are
+    /// missing. Events are kept in the order they are written, even if it's not sorted by
+    /// position, like [`Self::read`] does. Values the player can't play, such as a length of 0,
+    /// are kept as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::Syntax`] with the location of the first error if the text is not a
+    /// valid song.
+    pub fn from_text(text: &str) -> Result<Self, OrgError> {
+        let mut song = Self::default();
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, tokenize(line)))
+            .filter(|(_, tokens)| !tokens.is_empty());
+        let header = lines.next();
+        if !header
+            .as_ref()
+            .is_some_and(|(_, tokens)| tokens.iter().map(|t| t.text).eq(TEXT_HEADER.split(' ')))
+        {
+            let (line, tokens) = header.unwrap_or((1, Vec::new()));
+            let fields = Fields {
+                line,
+                tokens: &tokens,
+                index: 0,
+            };
+            return Err(fields.error_at(0, &format!("expected `{TEXT_HEADER}`")));
+        }
+        let mut channel = None;
+        for (line, tokens) in lines {
+            let mut fields = Fields {
+                line,
+                tokens: &tokens,
+                index: 0,
+            };
+            let first = &tokens[0];
+            match first.text {
+                "tempo_ms" => song.tempo_ms = fields.after_key()?,
+                "beats_per_measure" => song.beats_per_measure = fields.after_key()?,
+                "steps_per_beat" => song.steps_per_beat = fields.after_key()?,
+                "repeat_start" => song.repeat_start = fields.after_key()?,
+                "repeat_end" => song.repeat_end = fields.after_key()?,
+                "channel" => {
+                    fields.index = 1;
+                    let index: usize = fields.number()?;
+                    let Some(ch) = song.channels.get_mut(index) else {
+                        return Err(fields.error_at(1, "channel index must be below 16"));
+                    };
+                    fields.keyword("instrument")?;
+                    ch.instrument = fields.number()?;
+                    fields.keyword("finetune")?;
+                    ch.finetune = fields.number()?;
+                    fields.keyword("pizzicato")?;
+                    ch.pizzicato = match fields.number()? {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(fields.error_at(fields.index - 1, "expected 0 or 1")),
+                    };
+                    fields.end()?;
+                    ch.events.clear();
+                    channel = Some(index);
+                }
+                text if text.starts_with(|c: char| c.is_ascii_digit()) => {
+                    let Some(index) = channel else {
+                        return Err(fields.error_at(0, "event outside of a channel"));
+                    };
+                    let evt = Event {
+                        position: fields.number()?,
+                        pitch: fields.property()?,
+                        length: fields.number()?,
+                        volume: fields.property()?,
+                        pan: fields.property()?,
+                    };
+                    fields.end()?;
+                    song.channels[index].events.push(evt);
+                }
+                text => {
+                    return Err(fields.error_at(0, &format!("unknown keyword `{text}`")));
+                }
+            }
+        }
+        Ok(song)
+    }
+}
+
+/// Displays an event as a line of the text format, without the line break
+pub(crate) struct EventLine<'a>(pub(crate) &'a Event);
+
+impl std::fmt::Display for EventLine<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let property = |value: u8| {
+            if value == PROPERTY_UNUSED {
+                UNUSED.to_owned()
+            } else {
+                value.to_string()
+            }
+        };
+        let evt = self.0;
+        write!(
+            f,
+            "{} {} {} {} {}",
+            evt.position,
+            property(evt.pitch),
+            evt.length,
+            property(evt.volume),
+            property(evt.pan)
+        )
+    }
+}
+
+/// A whitespace separated word of a line
+struct Token<'a> {
+    text: &'a str,
+    /// Column of the first character, starting from 1
+    column: usize,
+}
+
+/// Split a line in tokens, leaving out comments
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let line = line.split_once('#').map_or(line, |(code, _)| code);
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, (offset, c)) in (1..).zip(line.char_indices().chain([(line.len(), ' ')])) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((offset, column)),
+            (Some((begin, begin_column)), true) => {
+                tokens.push(Token {
+                    text: &line[begin..offset],
+                    column: begin_column,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// Reads the tokens of a line in order
+struct Fields<'a, 'b> {
+    line: usize,
+    tokens: &'b [Token<'a>],
+    index: usize,
+}
+
+impl<'a> Fields<'a, '_> {
+    fn error_at(&self, index: usize, message: &str) -> OrgError {
+        let column = match self.tokens.get(index) {
+            Some(token) => token.column,
+            // Past the last token
+            None => self
+                .tokens
+                .last()
+                .map_or(1, |t| t.column + t.text.chars().count() + 1),
+        };
+        OrgError::Syntax(SyntaxError {
+            line: self.line,
+            column,
+            message: message.to_owned(),
+        })
+    }
+    fn next(&mut self, expected: &str) -> Result<&'a str, OrgError> {
+        let token = self
+            .tokens
+            .get(self.index)
+            .ok_or_else(|| self.error_at(self.index, &format!("expected {expected}")))?;
+        self.index += 1;
+        Ok(token.text)
+    }
+    fn number<T: FromStr>(&mut self) -> Result<T, OrgError> {
+        let text = self.next("a number")?;
+        text.parse()
+            .map_err(|_| self.error_at(self.index - 1, &format!("invalid number `{text}`")))
+    }
+    /// A property that is either unused, or a number
+    fn property(&mut self) -> Result<u8, OrgError> {
+        if self
+            .tokens
+            .get(self.index)
+            .is_some_and(|t| t.text == UNUSED)
+        {
+            self.index += 1;
+            return Ok(PROPERTY_UNUSED);
+        }
+        self.number()
+    }
+    fn keyword(&mut self, keyword: &str) -> Result<(), OrgError> {
+        if self.next(&format!("`{keyword}`"))? != keyword {
+            return Err(self.error_at(self.index - 1, &format!("expected `{keyword}`")));
+        }
+        Ok(())
+    }
+    /// The value of a `key value` line
+    fn after_key<T: FromStr>(&mut self) -> Result<T, OrgError> {
+        self.index = 1;
+        let value = self.number()?;
+        self.end()?;
+        Ok(value)
+    }
+    fn end(&self) -> Result<(), OrgError> {
+        if self.index < self.tokens.len() {
+            return Err(self.error_at(self.index, "unexpected text at the end of the line"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::random_song};
+
+    #[test]
+    fn round_trip() {
+        for seed in 0..8 {
+            let mut song = random_song(seed);
+            song.channels[1].pizzicato = true;
+            song.channels[2].finetune = 1200;
+            // Unsorted, and duplicate positions, which `Song::read` keeps
+            song.channels[3].events.reverse();
+            let copy = song.channels[4].events.clone();
+            song.channels[4].events.extend(copy);
+            // Values out of the playable range
+            song.channels[5].events.push(Event::default());
+            song.channels[12].events.push(Event {
+                position: u32::MAX,
+                pitch: 200,
+                length: 0,
+                volume: 254,
+                pan: 13,
+            });
+            assert_eq!(Song::from_text(&song.to_text()).unwrap(), song);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use {crate::song::Song, std::fmt};
+
+/// Reproduces how [`Player`](crate::Player) spaces out ticks in output sample frames
+///
+/// The player accumulates a fractional number of frames per tick, so the frame count of a tick
+/// depends on all the ticks before it.
+#[derive(Clone)]
+struct TickClock {
+    samples_per_tick: f64,
+    samples_to_next_tick: f64,
+}
+
+impl TickClock {
+    fn new(sample_rate: u16, tempo_ms: u16) -> Self {
+        Self {
+            samples_per_tick: f64::from(sample_rate) * f64::from(tempo_ms) / 1000.0,
+            samples_to_next_tick: 0.0,
+        }
+    }
+    /// Advance past the next tick, and return how many frames it lasts
+    fn next_tick(&mut self) -> u64 {
+        self.samples_to_next_tick += self.samples_per_tick;
+        // The player always spends at least the frame the tick happens on
+        let frames = self.samples_to_next_tick.ceil().max(1.0);
+        self.samples_to_next_tick -= frames;
+        // The frame count is a positive integer
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        {
+            frames as u64
+        }
+    }
+    /// Advance past `ticks` ticks, and return how many frames they last
+    fn skip_ticks(&mut self, ticks: u64) -> u64 {
+        (0..ticks).map(|_| self.next_tick()).sum()
+    }
+}
+
+/// A song position on the measure grid
+///
+/// All parts count from 0. Displays as `measure:beat:step`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MusicalTime {
+    /// Index of the measure
+    pub measure: u32,
+    /// Index of the beat in the measure
+    pub beat: u32,
+    /// Index of the step in the beat
+    pub step: u32,
+}
+
+impl fmt::Display for MusicalTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.measure, self.beat, self.step)
+    }
+}
+
+/// Converts between ticks, song positions, sample frames and seconds, like the player does
+///
+/// Ticks count every tick played since the beginning, while positions are where in the song a
+/// tick is, which goes back to the repeat start at the repeat end. Frames count from the
+/// beginning of the song, when the [`Player`](crate::Player) has just loaded it or restarted.
+/// Each tick happens on a whole frame, and ticks don't all last the same number of frames, so
+/// conversions between ticks and frames account for every tick before.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timing {
+    sample_rate: u16,
+    tempo_ms: u16,
+    steps_per_beat: u32,
+    measure_len: u32,
+    repeat_start: u32,
+    repeat_end: u32,
+}
+
+impl Timing {
+    /// The timing of `song`, played at `sample_rate`
+    #[must_use]
+    pub fn new(song: &Song, sample_rate: u16) -> Self {
+        Self {
+            sample_rate,
+            tempo_ms: song.tempo_ms,
+            steps_per_beat: u32::from(song.steps_per_beat).max(1),
+            measure_len: song.measure_len(),
+            repeat_start: song.repeat_start,
+            repeat_end: song.repeat_end,
+        }
+    }
+    /// The output sample rate in Hz
+    #[must_use]
+    pub const fn sample_rate(&self) -> u16 {
+        self.sample_rate
+    }
+    /// The average number of frames per tick
+    #[must_use]
+    pub fn frames_per_tick(&self) -> f64 {
+        f64::from(self.sample_rate) * f64::from(self.tempo_ms) / 1000.0
+    }
+    /// The frame at which each tick happens, in order
+    pub fn tick_frames(&self) -> impl Iterator<Item = u64> + use<> {
+        let mut clock = TickClock::new(self.sample_rate, self.tempo_ms);
+        let mut frame = 0;
+        std::iter::repeat_with(move || {
+            let tick_frame = frame;
+            frame += clock.next_tick();
+            tick_frame
+        })
+    }
+    /// The frame at which `tick` happens
+    ///
+    /// This is also how many frames the ticks before it last.
+    #[must_use]
+    pub fn tick_to_frame(&self, tick: u64) -> u64 {
+        TickClock::new(self.sample_rate, self.tempo_ms).skip_ticks(tick)
+    }
+    /// The tick playing at `frame`
+    #[must_use]
+    pub fn frame_to_tick(&self, frame: u64) -> u64 {
+        let mut clock = TickClock::new(self.sample_rate, self.tempo_ms);
+        let mut tick = 0;
+        let mut next_frame = clock.next_tick();
+        while next_frame <= frame {
+            tick += 1;
+            next_frame += clock.next_tick();
+        }
+        tick
+    }
+    /// The time at which `frame` plays, in seconds
+    #[must_use]
+    pub fn frame_to_seconds(&self, frame: u64) -> f64 {
+        // Precision loss only matters past 2^53 frames
+        #[expect(clippy::cast_precision_loss)]
+        let frame = frame as f64;
+        frame / f64::from(self.sample_rate)
+    }
+    /// The frame playing at `seconds`
+    #[must_use]
+    pub fn seconds_to_frame(&self, seconds: f64) -> u64 {
+        // Truncating a positive number of frames
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        {
+            (seconds.max(0.0) * f64::from(self.sample_rate)) as u64
+        }
+    }
+    /// The time at which `tick` happens, in seconds
+    #[must_use]
+    pub fn tick_to_seconds(&self, tick: u64) -> f64 {
+        self.frame_to_seconds(self.tick_to_frame(tick))
+    }
+    /// The tick playing at `seconds`
+    #[must_use]
+    pub fn seconds_to_tick(&self, seconds: f64) -> u64 {
+        self.frame_to_tick(self.seconds_to_frame(seconds))
+    }
+    /// Number of ticks before the song loops back for the first time
+    ///
+    /// The first tick always plays, even if the repeat end is 0.
+    #[must_use]
+    pub fn first_pass_ticks(&self) -> u64 {
+        u64::from(self.repeat_end.max(1))
+    }
+    /// Number of ticks of each loop from the repeat start
+    ///
+    /// A loop plays at least one tick, even if the repeat start is after the repeat end.
+    #[must_use]
+    pub fn loop_ticks(&self) -> u64 {
+        u64::from(self.repeat_end.max(self.repeat_start.saturating_add(1)) - self.repeat_start)
+    }
+    /// The song position played at `tick`
+    #[must_use]
+    pub fn tick_to_position(&self, tick: u64) -> u32 {
+        let first_pass = self.first_pass_ticks();
+        if tick < first_pass {
+            // Below the repeat end
+            return u32::try_from(tick).unwrap_or(u32::MAX);
+        }
+        let in_loop = (tick - first_pass) % self.loop_ticks();
+        // Below the loop length
+        self.repeat_start + u32::try_from(in_loop).unwrap_or(0)
+    }
+    /// The first tick that plays `position`, if it ever plays
+    #[must_use]
+    pub fn position_to_tick(&self, position: u32) -> Option<u64> {
+        let position = u64::from(position);
+        if position < self.first_pass_ticks() {
+            return Some(position);
+        }
+        let repeat_start = u64::from(self.repeat_start);
+        (position == repeat_start).then(|| self.first_pass_ticks())
+    }
+    /// Where `position` is on the measure grid
+    #[must_use]
+    pub const fn to_musical(&self, position: u32) -> MusicalTime {
+        let in_measure = position % self.measure_len;
+        MusicalTime {
+            measure: position / self.measure_len,
+            beat: in_measure / self.steps_per_beat,
+            step: in_measure % self.steps_per_beat,
+        }
+    }
+    /// The position of `time` on the measure grid
+    ///
+    /// Beats and steps past the end of their measure or beat carry over.
+    #[must_use]
+    pub const fn from_musical(&self, time: MusicalTime) -> u32 {
+        time.measure
+            .saturating_mul(self.measure_len)
+            .saturating_add(time.beat.saturating_mul(self.steps_per_beat))
+            .saturating_add(time.step)
+    }
+}
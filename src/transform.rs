@@ -0,0 +1,175 @@
+use crate::{
+    OrgError, PROPERTY_UNUSED,
+    song::{Event, Song},
+};
+
+/// Number of pitches a note can have
+const PITCH_COUNT: u8 = 96;
+
+/// What [`Song::transpose`] does with notes that end up outside the pitch range
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PitchOverflow {
+    /// Clamp them to the lowest or highest pitch
+    #[default]
+    Clamp,
+    /// Leave the song unchanged, and return an error
+    Reject,
+}
+
+/// What [`Song::scale_tempo`] keeps when changing the tempo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TempoScale {
+    /// Keep how long the song plays, by moving events to the new tick length
+    #[default]
+    KeepDuration,
+    /// Keep the events on the same ticks, so the song plays faster or slower
+    KeepTicks,
+}
+
+/// Transforms of the whole song
+///
+/// The repeat points move along with the events, so the song loops at the same place.
+impl Song {
+    /// Transpose the notes of `channels` by `semitones`
+    ///
+    /// Pitches must stay in `0..96`, what happens to the other ones depends on `overflow`.
+    ///
+    /// # Errors
+    ///
+    /// With [`PitchOverflow::Reject`], returns [`OrgError::PitchOutOfRange`] for the first note
+    /// that ends up out of range. The song is left unchanged.
+    ///
+    /// # Panics
+    ///
+    /// - If a channel index is not below 16
+    pub fn transpose(
+        &mut self,
+        channels: &[usize],
+        semitones: i16,
+        overflow: PitchOverflow,
+    ) -> Result<(), OrgError> {
+        let shift = |pitch: u8| i32::from(pitch) + i32::from(semitones);
+        if overflow == PitchOverflow::Reject {
+            for &channel in channels {
+                let out_of_range = self.channels[channel].events.iter().find(|e| {
+                    e.pitch != PROPERTY_UNUSED
+                        && !(0..i32::from(PITCH_COUNT)).contains(&shift(e.pitch))
+                });
+                if let Some(event) = out_of_range {
+                    return Err(OrgError::PitchOutOfRange {
+                        channel,
+                        position: event.position,
+                    });
+                }
+            }
+        }
+        for &channel in channels {
+            for event in &mut self.channels[channel].events {
+                if event.pitch != PROPERTY_UNUSED {
+                    event.pitch = clamp_u8(shift(event.pitch).into(), PITCH_COUNT - 1);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Change the number of steps per beat, keeping how the song sounds
+    ///
+    /// Positions and lengths are rescaled to the new grid, and the tempo changes so that beats
+    /// keep their duration. When the grid gets coarser, events that land on the same step are
+    /// merged, and later events take precedence. Events that change nothing are removed. Lengths
+    /// are kept below 256 ticks.
+    ///
+    /// # Panics
+    ///
+    /// - If `steps_per_beat` is 0
+    pub fn retime(&mut self, steps_per_beat: u8) {
+        assert!(steps_per_beat > 0, "steps per beat must not be 0");
+        let old = self.steps_per_beat.max(1);
+        self.rescale(u64::from(steps_per_beat), u64::from(old));
+        self.tempo_ms = scale_tempo(self.tempo_ms, u64::from(old), u64::from(steps_per_beat));
+        self.steps_per_beat = steps_per_beat;
+    }
+    /// Change the tempo to `tempo_ms` milliseconds per tick
+    ///
+    /// With [`TempoScale::KeepDuration`], positions and lengths are rescaled like in
+    /// [`Self::retime`], so the song plays for the same time. With [`TempoScale::KeepTicks`], only
+    /// the tempo changes.
+    ///
+    /// # Panics
+    ///
+    /// - If `tempo_ms` is 0
+    pub fn scale_tempo(&mut self, tempo_ms: u16, scale: TempoScale) {
+        assert!(tempo_ms > 0, "tempo must not be 0");
+        if scale == TempoScale::KeepDuration {
+            self.rescale(u64::from(self.tempo_ms.max(1)), u64::from(tempo_ms));
+        }
+        self.tempo_ms = tempo_ms;
+    }
+    /// Multiply every position, length and repeat point by `num / den`
+    ///
+    /// Note ends are rescaled rather than lengths, so notes stay aligned to each other.
+    fn rescale(&mut self, num: u64, den: u64) {
+        let position = |pos: u32| clamp_u32(u64::from(pos) * num / den);
+        for ch in &mut self.channels {
+            for event in &mut ch.events {
+                let start = position(event.position);
+                let end = position(event.position.saturating_add(event.length.into()));
+                *event = Event {
+                    position: start,
+                    length: clamp_u8(end.saturating_sub(start).max(1).into(), u8::MAX),
+                    ..*event
+                };
+            }
+            ch.normalize();
+        }
+        self.repeat_start = position(self.repeat_start);
+        self.repeat_end = position(self.repeat_end).max(self.repeat_start.saturating_add(1));
+    }
+}
+
+/// `tempo_ms * num / den`, rounded and kept in range
+fn scale_tempo(tempo_ms: u16, num: u64, den: u64) -> u16 {
+    let scaled = (u64::from(tempo_ms) * num + den / 2) / den;
+    u16::try_from(scaled.max(1)).unwrap_or(u16::MAX)
+}
+
+fn clamp_u8(value: i64, max: u8) -> u8 {
+    u8::try_from(value.clamp(0, i64::from(max))).unwrap_or(max)
+}
+
+fn clamp_u32(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::random_song};
+
+    #[test]
+    fn transpose_extreme_shifts() {
+        let song = random_song(1);
+        let pitches = |song: &Song| {
+            song.channels[..8]
+                .iter()
+                .flat_map(|ch| &ch.events)
+                .filter(|e| e.pitch != PROPERTY_UNUSED)
+                .map(|e| e.pitch)
+                .collect::<Vec<_>>()
+        };
+        assert!(!pitches(&song).is_empty());
+        let all: Vec<usize> = (0..8).collect();
+        for (semitones, pitch) in [(i16::MAX, PITCH_COUNT - 1), (i16::MIN, 0)] {
+            let mut clamped = song.clone();
+            clamped
+                .transpose(&all, semitones, PitchOverflow::Clamp)
+                .unwrap();
+            assert!(pitches(&clamped).iter().all(|&p| p == pitch));
+            let mut rejected = song.clone();
+            assert!(matches!(
+                rejected.transpose(&all, semitones, PitchOverflow::Reject),
+                Err(OrgError::PitchOutOfRange { .. })
+            ));
+            assert_eq!(rejected, song);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use {
+    crate::{
+        OrgError,
+        read_cursor::ReadCursor,
+        song::{Channel, Event, Song},
+    },
+    std::{iter::FusedIterator, ops::Range},
+};
+
+/// Size of the header, up to the event tables
+const HEADER_LEN: usize = 114;
+/// Size of an event in the event tables
+const EVENT_LEN: usize = 8;
+
+/// A view of an Organya song over borrowed bytes
+///
+/// The layout is validated once when the view is made. Reading the header and iterating over
+/// the events doesn't allocate. Values are cleaned up the same way [`Song::read`] does.
+#[derive(Clone, Copy, Debug)]
+pub struct SongView<'a> {
+    version: u8,
+    tempo_ms: u16,
+    beats_per_measure: u8,
+    steps_per_beat: u8,
+    repeat_start: u32,
+    repeat_end: u32,
+    channels: [ChannelView<'a>; 16],
+}
+
+impl<'a> SongView<'a> {
+    /// Make a view of Organya song data
+    ///
+    /// Data after the last event table is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OrgError::Malformed`] if the header is invalid, or the data is too short for
+    /// the events it declares.
+    pub fn new(data: &'a [u8]) -> Result<Self, OrgError> {
+        Self::new_inner(data).ok_or(OrgError::Malformed)
+    }
+    fn new_inner(data: &'a [u8]) -> Option<Self> {
+        let mut read = ReadCursor(data.get(..HEADER_LEN)?);
+        if read.next_bytes()? != b"Org-" {
+            return None;
+        }
+        let digits = read.next_bytes::<2>()?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let version = (digits[0] - b'0') * 10 + (digits[1] - b'0');
+        if !(1..=3).contains(&version) {
+            return None;
+        }
+        let tempo_ms = read.next_u16_le()?;
+        let beats_per_measure = read.next_u8()?;
+        let steps_per_beat = read.next_u8()?;
+        let repeat_start = read.next_u32_le()?;
+        let repeat_end = read.next_u32_le()?;
+        let mut headers = [(0, 0, false, 0); 16];
+        for (i, header) in headers.iter_mut().enumerate() {
+            let finetune = read.next_u16_le()?;
+            let mut instrument = read.next_u8()?;
+            let pizzicato = read.next_u8()? == 1 && version > 1;
+            // Drum instruments are resolved against the soundbank when the song is loaded
+            if i < 8 && instrument >= 100 {
+                instrument = 0;
+            }
+            let event_count = read.next_u16_le()?;
+            *header = (finetune, instrument, pizzicato, usize::from(event_count));
+        }
+        let mut tables = ReadCursor(&data[HEADER_LEN..]);
+        let mut channels = [ChannelView::default(); 16];
+        for (ch, (finetune, instrument, pizzicato, len)) in channels.iter_mut().zip(headers) {
+            *ch = ChannelView {
+                instrument,
+                finetune,
+                pizzicato,
+                table: tables.next_n_bytes(len * EVENT_LEN)?,
+            };
+        }
+        Some(Self {
+            version,
+            tempo_ms,
+            beats_per_measure,
+            steps_per_beat,
+            repeat_start,
+            repeat_end,
+            channels,
+        })
+    }
+    /// Version of the file format, from 1 to 3
+    #[must_use]
+    pub const fn version(&self) -> u8 {
+        self.version
+    }
+    /// Tempo of the song
+    #[must_use]
+    pub const fn tempo_ms(&self) -> u16 {
+        self.tempo_ms
+    }
+    /// Beats per measure
+    #[must_use]
+    pub const fn beats_per_measure(&self) -> u8 {
+        self.beats_per_measure
+    }
+    /// Steps per beat
+    #[must_use]
+    pub const fn steps_per_beat(&self) -> u8 {
+        self.steps_per_beat
+    }
+    /// The point at which the song starts repeating
+    #[must_use]
+    pub const fn repeat_start(&self) -> u32 {
+        self.repeat_start
+    }
+    /// The point at which the song ends
+    #[must_use]
+    pub const fn repeat_end(&self) -> u32 {
+        self.repeat_end
+    }
+    /// The 16 channels of the song. There are 8 melody, and 8 drum channels.
+    #[must_use]
+    pub const fn channels(&self) -> &[ChannelView<'a>; 16] {
+        &self.channels
+    }
+    /// Number of events in all channels
+    #[must_use]
+    pub fn event_count(&self) -> usize {
+        self.channels.iter().map(ChannelView::len).sum()
+    }
+    /// Copy the song out of the data
+    #[must_use]
+    pub fn to_song(&self) -> Song {
+        Song {
+            tempo_ms: self.tempo_ms,
+            repeat_start: self.repeat_start,
+            repeat_end: self.repeat_end,
+            channels: self.channels.map(ChannelView::to_channel),
+            beats_per_measure: self.beats_per_measure,
+            steps_per_beat: self.steps_per_beat,
+        }
+    }
+}
+
+/// A view of a channel, part of a [`SongView`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChannelView<'a> {
+    instrument: u8,
+    finetune: u16,
+    pizzicato: bool,
+    /// Positions, then pitches, lengths, volumes and pans of the events
+    table: &'a [u8],
+}
+
+impl<'a> ChannelView<'a> {
+    /// The index of the instrument in the instrument bank
+    #[must_use]
+    pub const fn instrument(&self) -> u8 {
+        self.instrument
+    }
+    /// The finetune of the channel
+    #[must_use]
+    pub const fn finetune(&self) -> u16 {
+        self.finetune
+    }
+    /// Whether the notes of the channel are played pizzicato
+    #[must_use]
+    pub const fn pizzicato(&self) -> bool {
+        self.pizzicato
+    }
+    /// Number of events
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.table.len() / EVENT_LEN
+    }
+    /// Whether the channel has no events
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+    /// The events, in the order they are stored in the file
+    #[must_use]
+    pub const fn events(&self) -> Events<'a> {
+        Events {
+            table: self.table,
+            range: 0..self.len(),
+        }
+    }
+    fn to_channel(self) -> Channel {
+        Channel {
+            instrument: self.instrument,
+            finetune: self.finetune,
+            pizzicato: self.pizzicato,
+            events: self.events().collect(),
+        }
+    }
+}
+
+/// Iterator over the events of a [`ChannelView`]
+#[derive(Clone, Debug)]
+pub struct Events<'a> {
+    table: &'a [u8],
+    range: Range<usize>,
+}
+
+impl Events<'_> {
+    /// Decode the event at `index`, which is in bounds
+    fn event(&self, index: usize) -> Event {
+        let len = self.table.len() / EVENT_LEN;
+        let mut pos = [0; 4];
+        pos.copy_from_slice(&self.table[index * 4..index * 4 + 4]);
+        let mut event = Event {
+            position: u32::from_le_bytes(pos),
+            pitch: self.table[len * 4 + index],
+            length: self.table[len * 5 + index],
+            volume: self.table[len * 6 + index],
+            pan: self.table[len * 7 + index],
+        };
+        event.clean_up();
+        event
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let index = self.range.next()?;
+        Some(self.event(index))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Event> {
+        let index = self.range.nth(n)?;
+        Some(self.event(index))
+    }
+}
+
+impl DoubleEndedIterator for Events<'_> {
+    fn next_back(&mut self) -> Option<Event> {
+        let index = self.range.next_back()?;
+        Some(self.event(index))
+    }
+}
+
+impl ExactSizeIterator for Events<'_> {}
+
+impl FusedIterator for Events<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{PROPERTY_UNUSED, test_util::Rng},
+    };
+
+    /// Organya data of version 2, with random events including values the player can't play
+    fn random_data(seed: u64) -> Vec<u8> {
+        let mut rng = Rng::new(seed);
+        let counts: Vec<u16> = (0..16)
+            .map(|_| [0, 1, 5, 40][rng.below(4) as usize])
+            .collect();
+        let mut data = b"Org-02".to_vec();
+        data.extend_from_slice(&125u16.to_le_bytes());
+        data.extend_from_slice(&[4, 4]);
+        data.extend_from_slice(&rng.below(100).to_le_bytes());
+        data.extend_from_slice(&(100 + rng.below(100)).to_le_bytes());
+        for &count in &counts {
+            data.extend_from_slice(&(900 + rng.below(200)).to_le_bytes()[..2]);
+            data.extend_from_slice(&[rng.byte_below(255), rng.byte_below(3)]);
+            data.extend_from_slice(&count.to_le_bytes());
+        }
+        for count in counts {
+            let mut position = 0;
+            for _ in 0..count {
+                // Unsorted at times
+                position = (position + rng.below(8)).saturating_sub(rng.below(3));
+                data.extend_from_slice(&position.to_le_bytes());
+            }
+            for _ in 0..4 * count {
+                data.push(if rng.chance(20) {
+                    PROPERTY_UNUSED
+                } else {
+                    rng.byte_below(255)
+                });
+            }
+        }
+        data
+    }
+
+    /// `Song::read` as it was before it used `SongView`
+    fn read_reference(data: &[u8]) -> Song {
+        let mut song = Song::default();
+        let mut read = ReadCursor(data);
+        read.next_bytes::<4>();
+        let version = (read.next_u8().unwrap() - b'0') * 10 + (read.next_u8().unwrap() - b'0');
+        song.tempo_ms = read.next_u16_le().unwrap();
+        song.beats_per_measure = read.next_u8().unwrap();
+        song.steps_per_beat = read.next_u8().unwrap();
+        song.repeat_start = read.next_u32_le().unwrap();
+        song.repeat_end = read.next_u32_le().unwrap();
+        for (i, ch) in song.channels.iter_mut().enumerate() {
+            ch.finetune = read.next_u16_le().unwrap();
+            ch.instrument = read.next_u8().unwrap();
+            ch.pizzicato = read.next_u8().unwrap() == 1 && version > 1;
+            if i < 8 && ch.instrument >= 100 {
+                ch.instrument = 0;
+            }
+            let count = read.next_u16_le().unwrap();
+            ch.events = vec![Event::default(); usize::from(count)];
+        }
+        for ch in &mut song.channels {
+            let len = ch.events.len();
+            let table = read.next_n_bytes(len * 8).unwrap();
+            for (j, evt) in ch.events.iter_mut().enumerate() {
+                evt.position = u32::from_le_bytes(table[j * 4..][..4].try_into().unwrap());
+                evt.pitch = table[len * 4 + j];
+                evt.length = table[len * 5 + j];
+                evt.volume = table[len * 6 + j];
+                evt.pan = table[len * 7 + j];
+                if evt.pitch >= 96 {
+                    evt.pitch = PROPERTY_UNUSED;
+                }
+                if evt.length == 0 {
+                    evt.length = 1;
+                }
+                if evt.pan > 12 && evt.pan != PROPERTY_UNUSED {
+                    evt.pan = 6;
+                }
+            }
+        }
+        song
+    }
+
+    #[test]
+    fn matches_reference_reader() {
+        for seed in 0..16 {
+            let data = random_data(seed);
+            let expected = read_reference(&data);
+            let view = SongView::new(&data).unwrap();
+            assert_eq!(view.to_song(), expected);
+            assert_eq!(view.version(), 2);
+            for (ch, expected) in view.channels().iter().zip(&expected.channels) {
+                assert_eq!(ch.len(), expected.events.len());
+                assert!(ch.events().eq(expected.events.iter().copied()));
+                assert!(ch.events().rev().eq(expected.events.iter().rev().copied()));
+            }
+            let mut song = Song::default();
+            song.read(&data).unwrap();
+            assert_eq!(song, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_data() {
+        let data = random_data(7);
+        for len in 0..data.len() {
+            assert!(SongView::new(&data[..len]).is_err(), "length {len}");
+        }
+        for (index, byte) in [(0, b'X'), (4, b'/'), (5, b'9'), (4, 0xFF)] {
+            let mut data = data.clone();
+            data[index] = byte;
+            assert!(SongView::new(&data).is_err());
+            // A failed read leaves the song unchanged
+            let mut song = Song::default();
+            assert!(song.read(&data).is_err());
+            assert_eq!(song, Song::default());
+        }
+        // Trailing data is ignored
+        assert!(SongView::new(&[&data[..], &[1, 2, 3]].concat()).is_ok());
+    }
+}
@@ -0,0 +1,277 @@
+use {
+    crate::{Interpolation, player::Player},
+    std::{
+        io::{self, Write},
+        time::Duration,
+    },
+};
+
+/// How many frames to render at once
+const CHUNK_FRAMES: usize = 4096;
+
+/// Sample format of a WAV file
+#[derive(Clone, Copy, Default)]
+pub enum WavFormat {
+    /// 32 bit IEEE floating point
+    #[default]
+    Float,
+    /// 16 bit signed integer PCM
+    Pcm16,
+}
+
+/// Options for [`Player::write_wav`]
+#[derive(Clone)]
+pub struct WavOptions {
+    /// Sample format of the file
+    pub format: WavFormat,
+    /// How many times the looping part of the song is played. With 0, only the intro is played.
+    ///
+    /// When the song loops, it's played at least twice, so the loop points mark a seamless pass.
+    pub loops: u32,
+    /// How long the song keeps playing after the last loop while fading out
+    pub fade_out: Duration,
+    /// How much silence is appended at the end
+    pub silence: Duration,
+    /// Interpolation used when rendering
+    pub interpolation: Interpolation,
+}
+
+impl Default for WavOptions {
+    fn default() -> Self {
+        Self {
+            format: WavFormat::default(),
+            loops: 1,
+            fade_out: Duration::ZERO,
+            silence: Duration::ZERO,
+            interpolation: Interpolation::Lagrange,
+        }
+    }
+}
+
+impl Player {
+    /// Render the current song from the beginning, and write it as a stereo RIFF WAVE file
+    ///
+    /// The loop points of the song are written in a `smpl` chunk, so the file can be looped
+    /// by game engines. They mark the second pass of the loop, which starts with the tails of
+    /// the notes of the first pass, so the loop is seamless. A second pass is rendered even
+    /// with 1 loop.
+    ///
+    /// # Errors
+    ///
+    /// - Returns any error that `writer` returns.
+    /// - Returns [`io::ErrorKind::InvalidInput`] if the audio is too large for a WAV file.
+    pub fn write_wav<W: Write>(&mut self, mut writer: W, options: &WavOptions) -> io::Result<()> {
+        self.restart();
+        let song = self.song();
+        let sample_rate = self.sample_rate();
+        let timing = self.timing();
+        let loop_ticks = u64::from(song.repeat_end.saturating_sub(song.repeat_start));
+        let intro_ticks = u64::from(song.repeat_start.min(song.repeat_end));
+        let loop_frame = |pass: u64| timing.tick_to_frame(intro_ticks + loop_ticks * pass);
+        let loops = match options.loops {
+            // The first pass starts with the tails of the intro instead of the end of the loop
+            1 if loop_ticks > 0 => 2,
+            loops => u64::from(loops),
+        };
+        let song_frames = loop_frame(loops);
+        let (loop_start, loop_end) = (loop_frame(1), loop_frame(2));
+        let secs_to_frames = |duration: Duration| {
+            // Rounding a positive duration to whole frames
+            #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                (duration.as_secs_f64() * f64::from(sample_rate)).round() as u64
+            }
+        };
+        let fade_frames = secs_to_frames(options.fade_out);
+        let silence_frames = secs_to_frames(options.silence);
+        let total_frames = song_frames + fade_frames + silence_frames;
+
+        let loop_points = (loops > 0 && loop_ticks > 0).then_some((loop_start, loop_end));
+        let header = wav_header(options.format, sample_rate, total_frames, loop_points)?;
+        writer.write_all(&header)?;
+
+        let mut buf = vec![0.0; CHUNK_FRAMES * 2];
+        let mut bytes = Vec::new();
+        let mut frame = 0;
+        while frame < total_frames {
+            let frames =
+                CHUNK_FRAMES.min(usize::try_from(total_frames - frame).unwrap_or(usize::MAX));
+            let buf = &mut buf[..frames * 2];
+            self.write_next(buf, options.interpolation);
+            for (i, [l, r]) in buf.as_chunks_mut().0.iter_mut().enumerate() {
+                let frame = frame + i as u64;
+                let gain = if frame < song_frames {
+                    1.0
+                } else if frame < song_frames + fade_frames {
+                    // Precision loss is fine for a gain factor
+                    #[expect(clippy::cast_precision_loss)]
+                    {
+                        1.0 - (frame - song_frames) as f32 / fade_frames as f32
+                    }
+                } else {
+                    0.0
+                };
+                *l *= gain;
+                *r *= gain;
+            }
+            bytes.clear();
+            match options.format {
+                WavFormat::Float => bytes.extend(buf.iter().flat_map(|s| s.to_le_bytes())),
+                WavFormat::Pcm16 => {
+                    bytes.extend(buf.iter().flat_map(|&s| to_pcm16(s).to_le_bytes()));
+                }
+            }
+            writer.write_all(&bytes)?;
+            frame += frames as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Sample format tag and size in bytes of a sample
+const fn format_info(format: WavFormat) -> (u16, u16) {
+    match format {
+        WavFormat::Float => (3, 4),
+        WavFormat::Pcm16 => (1, 2),
+    }
+}
+
+fn wav_header(
+    format: WavFormat,
+    sample_rate: u16,
+    total_frames: u64,
+    loop_points: Option<(u64, u64)>,
+) -> io::Result<Vec<u8>> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "audio too large for WAV");
+    let (format_tag, sample_size) = format_info(format);
+    let block_align = sample_size * 2;
+    let data_size =
+        u32::try_from(total_frames * u64::from(block_align)).map_err(|_| too_large())?;
+    let smpl_size = if loop_points.is_some() { 8 + 60 } else { 0 };
+    // Float files carry the `cbSize` field and a `fact` chunk
+    let (fmt_size, fact_size): (u32, u32) = match format {
+        WavFormat::Float => (18, 8 + 4),
+        WavFormat::Pcm16 => (16, 0),
+    };
+    let riff_size = (4 + 8 + fmt_size + fact_size + smpl_size + 8)
+        .checked_add(data_size)
+        .ok_or_else(too_large)?;
+
+    let mut header = Vec::new();
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_size.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&fmt_size.to_le_bytes());
+    header.extend_from_slice(&format_tag.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&u32::from(sample_rate).to_le_bytes());
+    header.extend_from_slice(&(u32::from(sample_rate) * u32::from(block_align)).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&(sample_size * 8).to_le_bytes());
+    if let WavFormat::Float = format {
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(b"fact");
+        header.extend_from_slice(&4u32.to_le_bytes());
+        header.extend_from_slice(
+            &u32::try_from(total_frames)
+                .map_err(|_| too_large())?
+                .to_le_bytes(),
+        );
+    }
+    if let Some((start, end)) = loop_points {
+        let start = u32::try_from(start).map_err(|_| too_large())?;
+        let end = u32::try_from(end - 1).map_err(|_| too_large())?;
+        header.extend_from_slice(b"smpl");
+        header.extend_from_slice(&60u32.to_le_bytes());
+        // Manufacturer, product
+        header.extend_from_slice(&[0; 8]);
+        // Sample period in nanoseconds
+        header.extend_from_slice(&(1_000_000_000 / u32::from(sample_rate)).to_le_bytes());
+        // MIDI unity note, pitch fraction, SMPTE format, SMPTE offset
+        header.extend_from_slice(&60u32.to_le_bytes());
+        header.extend_from_slice(&[0; 12]);
+        // Loop count, sampler data
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        // Cue point id, forward loop, start, inclusive end, fraction, infinite play count
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&start.to_le_bytes());
+        header.extend_from_slice(&end.to_le_bytes());
+        header.extend_from_slice(&[0; 8]);
+    }
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    Ok(header)
+}
+
+fn to_pcm16(sample: f32) -> i16 {
+    // The sample is clamped to the i16 range before casting
+    #[expect(clippy::cast_possible_truncation)]
+    {
+        (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_util::random_song};
+
+    /// The loop start and inclusive end of the `smpl` chunk
+    fn loop_points(wav: &[u8]) -> (u32, u32) {
+        let smpl = wav.windows(4).position(|w| w == b"smpl").unwrap();
+        let at = |offset| u32::from_le_bytes(wav[smpl + offset..][..4].try_into().unwrap());
+        (at(52), at(56))
+    }
+
+    #[test]
+    fn loop_points_skip_first_pass() {
+        let song = random_song(5);
+        let mut player = Player::default();
+        player.set_song(song.clone());
+        let timing = player.timing();
+        let frame = |ticks: u32| u32::try_from(timing.tick_to_frame(ticks.into())).unwrap();
+        let loop_ticks = song.repeat_end - song.repeat_start;
+        let mut points = |loops| {
+            let mut wav = Vec::new();
+            let options = WavOptions {
+                loops,
+                ..WavOptions::default()
+            };
+            player.write_wav(&mut wav, &options).unwrap();
+            loop_points(&wav)
+        };
+        let second_pass = (
+            frame(song.repeat_end),
+            frame(song.repeat_end + loop_ticks) - 1,
+        );
+        assert_eq!(points(1), second_pass);
+        assert_eq!(points(3), second_pass);
+    }
+
+    #[test]
+    fn one_loop_renders_two_passes() {
+        let song = random_song(5);
+        let mut player = Player::default();
+        player.set_song(song.clone());
+        let timing = player.timing();
+        let loop_ticks = song.repeat_end - song.repeat_start;
+        let mut data_len = |loops| {
+            let mut wav = Vec::new();
+            let options = WavOptions {
+                loops,
+                format: WavFormat::Pcm16,
+                ..WavOptions::default()
+            };
+            player.write_wav(&mut wav, &options).unwrap();
+            let data = wav.windows(4).position(|w| w == b"data").unwrap();
+            u64::from(u32::from_le_bytes(wav[data + 4..][..4].try_into().unwrap()))
+        };
+        let frames = timing.tick_to_frame((song.repeat_end + loop_ticks).into());
+        assert_eq!(data_len(1), frames * 4);
+        assert_eq!(data_len(2), frames * 4);
+        assert_eq!(
+            data_len(0),
+            timing.tick_to_frame(song.repeat_start.into()) * 4
+        );
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{
+    PROPERTY_UNUSED,
+    player::{PANNING_TABLE, volume_db},
+    song::{Channel, Song},
+    soundbank::{MELODY_WAVE_LEN, SampleFormat, Samples, Soundbank},
+};
+
+/// Highest note number in XM patterns
+const MAX_NOTE: u8 = 96;
+/// XM note that releases the playing note
+const NOTE_OFF: u8 = 97;
+/// Sample rate XM plays samples at for C-4, with no relative note or finetune
+const XM_C4_RATE: f64 = 8363.0;
+/// Sample rate that plays a melody wave at the pitch of Organya pitch 48
+///
+/// That's the frequency of C in the 4th octave of the frequency table, times the wave length.
+const MELODY_C4_RATE: f64 = 524.0 * 256.0;
+/// How many periods of the melody wave pizzicato samples last
+const PIZZICATO_PERIODS: usize = 16;
+/// Number of channels in the module
+const CHANNEL_COUNT: u16 = 16;
+/// Maximum number of rows in a pattern
+const MAX_ROWS: u32 = 256;
+/// Maximum number of entries in the pattern order table
+const MAX_ORDERS: usize = 256;
+/// Maximum number of rows the order table can play
+const MAX_MODULE_ROWS: u32 = 256 * MAX_ROWS;
+/// XM panning effect
+const EFFECT_PAN: u8 = 0x08;
+
+/// A sample used by an XM instrument
+#[derive(PartialEq)]
+enum XmSample {
+    /// A melody wave, either looping or played as pizzicato
+    Melody { wave: u8, pizzicato: bool },
+    /// A drum, played once
+    Drum(u8),
+}
+
+/// The patterns of a module, and the order they play in
+#[derive(Default)]
+struct PatternLayout {
+    orders: Vec<u8>,
+    /// Index in `orders` the module restarts at
+    restart: usize,
+    /// Row count and packed data of each pattern
+    patterns: Vec<(u32, Vec<u8>)>,
+}
+
+impl PatternLayout {
+    /// Write the module header followed by the patterns
+    fn write(&self, out: &mut Vec<u8>, instrument_count: usize, tempo_ms: u16) {
+        let (speed, bpm) = speed_and_bpm(tempo_ms);
+        let to_u16 = |n: usize| u16::try_from(n).unwrap_or(u16::MAX);
+        out.extend_from_slice(b"Extended Module: ");
+        out.extend_from_slice(&padded_name("", 20));
+        out.push(0x1A);
+        out.extend_from_slice(&padded_name("organyacat", 20));
+        out.extend_from_slice(&0x0104u16.to_le_bytes());
+        out.extend_from_slice(&276u32.to_le_bytes());
+        for value in [
+            to_u16(self.orders.len()),
+            to_u16(self.restart),
+            CHANNEL_COUNT,
+            to_u16(self.patterns.len()),
+            to_u16(instrument_count),
+            // Linear frequency table
+            1,
+            speed,
+            bpm,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut order_table = [0u8; MAX_ORDERS];
+        order_table[..self.orders.len()].copy_from_slice(&self.orders);
+        out.extend_from_slice(&order_table);
+        for (rows, pattern) in &self.patterns {
+            out.extend_from_slice(&9u32.to_le_bytes());
+            out.push(0);
+            out.extend_from_slice(&to_u16(*rows as usize).to_le_bytes());
+            out.extend_from_slice(&to_u16(pattern.len()).to_le_bytes());
+            out.extend_from_slice(pattern);
+        }
+    }
+}
+
+/// A cell of an XM pattern. Zero fields are empty.
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Cell {
+    note: u8,
+    instrument: u8,
+    volume: u8,
+    effect: u8,
+    param: u8,
+}
+
+impl Song {
+    /// Convert the song to a `FastTracker` 2 XM module, using the samples of `soundbank`
+    ///
+    /// Each Organya tick becomes a row, and each channel becomes a module channel. Melody waves
+    /// become looped samples, except on pizzicato channels, where they become one-shot samples
+    /// of a few periods. Drums become one-shot samples. The intro and the loop are laid out in
+    /// separate patterns, so the restart position starts the loop. Events at or after the
+    /// repeat end are not exported.
+    ///
+    /// The conversion is approximate. The finetune of channels is not exported, and drum
+    /// pitches are rounded to the nearest semitone.
+    #[must_use]
+    pub fn to_xm(&self, soundbank: &Soundbank) -> Vec<u8> {
+        let mut samples = Vec::new();
+        let mut instruments = [0u8; 16];
+        for ((i, ch), instrument) in self.channels.iter().enumerate().zip(&mut instruments) {
+            if ch.events.is_empty() {
+                continue;
+            }
+            let sample = if i < 8 {
+                XmSample::Melody {
+                    wave: ch.instrument,
+                    pizzicato: ch.pizzicato,
+                }
+            } else {
+                XmSample::Drum(ch.instrument)
+            };
+            let index = samples
+                .iter()
+                .position(|s| *s == sample)
+                .unwrap_or_else(|| {
+                    samples.push(sample);
+                    samples.len() - 1
+                });
+            // Instruments are numbered from 1, and there are at most 16
+            *instrument = (1u8..).nth(index).unwrap_or(u8::MAX);
+        }
+        let layout = self.pattern_layout(&instruments);
+        let mut out = Vec::new();
+        layout.write(&mut out, samples.len(), self.tempo_ms);
+        for sample in &samples {
+            write_instrument(&mut out, sample, soundbank);
+        }
+        out
+    }
+
+    /// Split the rows of the song into patterns, with the intro and the loop in separate ones
+    fn pattern_layout(&self, instruments: &[u8; 16]) -> PatternLayout {
+        let end = self.repeat_end.min(MAX_MODULE_ROWS);
+        let start = self.repeat_start.min(end);
+        let measure = (u32::from(self.beats_per_measure) * u32::from(self.steps_per_beat)).max(1);
+        let mut rows_per_pattern = (measure * (64 / measure).max(1)).min(MAX_ROWS);
+        let chunks = |rows: u32| start.div_ceil(rows) + (end - start).div_ceil(rows);
+        if chunks(rows_per_pattern) as usize > MAX_ORDERS {
+            rows_per_pattern = MAX_ROWS;
+        }
+        let grid = self.cell_grid(instruments, end);
+        let mut layout = PatternLayout::default();
+        for (segment_start, segment_end) in [(0, start), (start, end)] {
+            if segment_start == start {
+                layout.restart = layout.orders.len();
+            }
+            let mut row = segment_start;
+            while row < segment_end && layout.orders.len() < MAX_ORDERS {
+                let rows = (segment_end - row).min(rows_per_pattern);
+                let pattern = (rows, pack_pattern(&grid, row, rows));
+                // Identical patterns are shared
+                let patterns = &mut layout.patterns;
+                let index = patterns
+                    .iter()
+                    .position(|p| *p == pattern)
+                    .unwrap_or_else(|| {
+                        patterns.push(pattern);
+                        patterns.len() - 1
+                    });
+                layout.orders.push(u8::try_from(index).unwrap_or(u8::MAX));
+                row += rows;
+            }
+        }
+        layout
+    }
+
+    /// Lay out the events of every channel as cells, one row per tick up to the repeat end
+    fn cell_grid(&self, instruments: &[u8; 16], end: u32) -> Vec<[Cell; 16]> {
+        let mut grid = vec![[Cell::default(); 16]; end as usize];
+        for (i, ch) in self.channels.iter().enumerate() {
+            let is_drum = i >= 8;
+            // Sounds play at full volume before the first volume event
+            let mut volume = 64;
+            for (j, evt) in ch.events.iter().enumerate() {
+                let Some(row) = grid.get_mut(evt.position as usize) else {
+                    break;
+                };
+                let cell = &mut row[i];
+                if evt.volume != PROPERTY_UNUSED {
+                    volume = xm_volume(evt.volume);
+                    cell.volume = 0x10 + volume;
+                }
+                if evt.pan != PROPERTY_UNUSED {
+                    cell.effect = EFFECT_PAN;
+                    let pan = i32::from(PANNING_TABLE[usize::from(evt.pan.min(12))]) * 255 / 512;
+                    cell.param = u8::try_from(pan).unwrap();
+                }
+                if evt.pitch == PROPERTY_UNUSED {
+                    continue;
+                }
+                cell.note = if is_drum {
+                    drum_note(evt.pitch)
+                } else {
+                    evt.pitch.min(MAX_NOTE - 1) + 1
+                };
+                cell.instrument = instruments[i];
+                cell.volume = 0x10 + volume;
+                if is_drum || ch.pizzicato {
+                    continue;
+                }
+                let release = evt.position + u32::from(evt.length);
+                if note_released(ch, j, release)
+                    && let Some(row) = grid.get_mut(release as usize)
+                    && row[i].note == 0
+                {
+                    row[i].note = NOTE_OFF;
+                }
+            }
+        }
+        grid
+    }
+}
+
+/// Whether the note of `ch.events[index]` is still playing when it's released at `release`
+fn note_released(ch: &Channel, index: usize, release: u32) -> bool {
+    ch.events[index + 1..]
+        .iter()
+        .take_while(|e| e.position < release)
+        .all(|e| e.pitch == PROPERTY_UNUSED)
+}
+
+/// Pack `rows` rows of the grid starting at `first_row` with the XM packing scheme
+fn pack_pattern(grid: &[[Cell; 16]], first_row: u32, rows: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    for row in &grid[first_row as usize..(first_row + rows) as usize] {
+        for cell in row {
+            let fields = [
+                cell.note,
+                cell.instrument,
+                cell.volume,
+                cell.effect,
+                cell.param,
+            ];
+            let mut flags = 0x80;
+            for (bit, &field) in fields.iter().enumerate() {
+                if field != 0 {
+                    flags |= 1 << bit;
+                }
+            }
+            out.push(flags);
+            out.extend(fields.into_iter().filter(|&field| field != 0));
+        }
+    }
+    out
+}
+
+/// The XM volume (`0..=64`) with the same gain as an Organya volume
+fn xm_volume(volume: u8) -> u8 {
+    let gain = 10f64.powf(f64::from(volume_db(volume).clamp(-10000, 0)) / 2000.0);
+    // The gain is at most 1
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    {
+        (gain * 64.0).round() as u8
+    }
+}
+
+/// The XM note that plays a drum sample at the rate Organya plays it for `pitch`
+fn drum_note(pitch: u8) -> u8 {
+    let rate = f64::from(pitch).mul_add(800.0, 100.0);
+    let note = 12.0f64.mul_add((rate / XM_C4_RATE).log2(), 49.0);
+    // The note is clamped to the valid range
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    {
+        note.round().clamp(1.0, f64::from(MAX_NOTE)) as u8
+    }
+}
+
+/// Find the speed (ticks per row) and BPM that give rows closest to `tempo_ms` milliseconds
+fn speed_and_bpm(tempo_ms: u16) -> (u16, u16) {
+    let tempo_ms = f64::from(tempo_ms.max(1));
+    let mut best = (6, 125);
+    let mut best_error = f64::INFINITY;
+    for speed in 1..=31u16 {
+        // A row lasts speed * 2.5 / bpm seconds
+        let bpm = (2500.0 * f64::from(speed) / tempo_ms)
+            .round()
+            .clamp(32.0, 255.0);
+        let error = (2500.0 * f64::from(speed) / bpm - tempo_ms).abs();
+        if error < best_error {
+            best_error = error;
+            // The BPM is clamped to the valid range
+            #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                best = (speed, bpm as u16);
+            }
+        }
+    }
+    best
+}
+
+fn padded_name(name: &str, len: usize) -> Vec<u8> {
+    let mut bytes = name.as_bytes().to_vec();
+    bytes.resize(len, 0);
+    bytes
+}
+
+fn write_instrument(out: &mut Vec<u8>, sample: &XmSample, soundbank: &Soundbank) {
+    let silence = Samples::default();
+    let (name, data, looping, rate) = match *sample {
+        XmSample::Melody { wave, pizzicato } => {
+            let data = soundbank
+                .melody_waves
+                .get(usize::from(wave))
+                .unwrap_or(&silence);
+            let mut data: Vec<f32> = (0..MELODY_WAVE_LEN).map(|i| data.get(i)).collect();
+            if pizzicato {
+                data = data.repeat(PIZZICATO_PERIODS);
+            }
+            // Organya pitch 48 is written as C-4, which plays at this rate
+            (format!("Wave {wave}"), data, !pizzicato, MELODY_C4_RATE)
+        }
+        XmSample::Drum(drum) => {
+            let data = soundbank.drums.get(usize::from(drum)).unwrap_or(&silence);
+            let data = (0..data.len()).map(|i| data.get(i)).collect();
+            (format!("Drum {drum}"), data, false, XM_C4_RATE)
+        }
+    };
+    let sixteen_bit = match *sample {
+        XmSample::Melody { wave, .. } => soundbank.melody_waves.get(usize::from(wave)),
+        XmSample::Drum(drum) => soundbank.drums.get(usize::from(drum)),
+    }
+    .is_some_and(|s| s.format() != SampleFormat::I8);
+
+    // Instrument header, with an unused envelope and vibrato
+    let mut header = Vec::new();
+    header.extend_from_slice(&263u32.to_le_bytes());
+    header.extend_from_slice(&padded_name(&name, 22));
+    header.push(0);
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&40u32.to_le_bytes());
+    header.resize(263, 0);
+    out.extend_from_slice(&header);
+
+    let sample_size = if sixteen_bit { 2 } else { 1 };
+    let len = u32::try_from(data.len() * sample_size).unwrap_or(u32::MAX);
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(if looping { len } else { 0 }).to_le_bytes());
+    // Volume
+    out.push(64);
+    let relative = 12.0 * (rate / XM_C4_RATE).log2();
+    let note = relative.floor();
+    // The finetune is in 1/128th of a semitone, and the relative note is in range
+    #[expect(clippy::cast_possible_truncation)]
+    {
+        out.push((((relative - note) * 128.0).round().min(127.0) as i8).cast_unsigned());
+    }
+    out.push(u8::from(looping) | if sixteen_bit { 0x10 } else { 0 });
+    // Panning
+    out.push(128);
+    #[expect(clippy::cast_possible_truncation)]
+    {
+        out.push((note as i8).cast_unsigned());
+    }
+    out.push(0);
+    out.extend_from_slice(&padded_name(&name, 22));
+
+    // Sample data is delta encoded
+    if sixteen_bit {
+        let mut last = 0i16;
+        for &s in &data {
+            // The sample is clamped to the i16 range
+            #[expect(clippy::cast_possible_truncation)]
+            let s = (s.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+            out.extend_from_slice(&s.wrapping_sub(last).to_le_bytes());
+            last = s;
+        }
+    } else {
+        let mut last = 0i8;
+        for &s in &data {
+            // The sample is clamped to the i8 range
+            #[expect(clippy::cast_possible_truncation)]
+            let s = (s * 128.0).round().clamp(-128.0, 127.0) as i8;
+            out.push(s.wrapping_sub(last).to_ne_bytes()[0]);
+            last = s;
+        }
+    }
+}